@@ -1,16 +1,30 @@
 pub mod alternatives;
+pub mod analyzed;
+pub mod byte_set;
 pub mod char_set;
+pub mod control_verb;
 pub mod element;
+pub mod emit;
 pub mod flags;
 pub mod groups;
+#[cfg(feature = "regex-syntax")]
+pub mod hir;
+pub mod json;
 pub mod pattern;
 pub mod quantified;
 pub mod quantifiers;
+pub mod token;
 
 pub use alternatives::Alternatives;
+pub use analyzed::AnalyzedPattern;
+pub use byte_set::{BytePattern, ByteSet};
 pub use char_set::{CClass, CharClass, CharSet};
-pub use element::{Element, Literal, ZeroWidthLiteral};
+pub use control_verb::VerbKind;
+pub use element::{BoundaryKind, Element, ElementRef, Literal, ZeroWidthLiteral};
+pub use emit::{AnchorStyle, BackrefStyle, EmitOptions, EscapeContext, NamedGroupStyle};
 pub use flags::{Flag, Flags};
-pub use groups::{Group, GroupExt};
+pub use groups::{Group, GroupExt, TernaryGroupId};
+pub use json::Json;
 pub use pattern::Pattern;
 pub use quantifiers::Quantifier;
+pub use token::Token;
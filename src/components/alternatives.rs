@@ -1,16 +1,13 @@
 use crate::{
-    components::{
-        Flags,
-        pattern::{Pattern, SubPattern},
-    },
+    components::{EmitOptions, pattern::SubPattern},
     parser::Rule,
 };
 use anyhow::Result;
 use pest::iterators::Pair;
-use std::fmt::Write;
+use std::collections::BTreeSet;
 
-#[derive(Clone, Debug)]
-pub struct Alternatives(Vec<SubPattern>);
+#[derive(Clone, Debug, PartialEq, Hash)]
+pub struct Alternatives(pub(crate) Vec<SubPattern>);
 
 impl Alternatives {
     pub fn from_pair(pair: Pair<Rule>) -> Result<Self> {
@@ -32,18 +29,14 @@ impl Alternatives {
         Self(components)
     }
     pub fn as_string(&self) -> String {
-        let cl = self.0.len();
-        let mut cs = self.0.iter();
-        let mut s = format!("{}|", cs.next().unwrap().as_string());
-        let mut e = cs.enumerate();
-        while let Some((ix, sp)) = e.next() {
-            if ix + 1 >= cl {
-                write!(s, "{}", sp.as_string()).unwrap();
-            } else {
-                write!(s, "{}|", sp.as_string()).unwrap();
-            }
-        }
-        s
+        self.as_string_with(&EmitOptions::default())
+    }
+    pub fn as_string_with(&self, opts: &EmitOptions) -> String {
+        self.0
+            .iter()
+            .map(|sp| sp.as_string_with(opts))
+            .collect::<Vec<String>>()
+            .join("|")
     }
     pub fn is_finite(&self) -> bool {
         for sp in self.0.iter() {
@@ -63,21 +56,72 @@ impl Alternatives {
         }
         min
     }
+    pub fn max_match_len(&self) -> Option<usize> {
+        self.0
+            .iter()
+            .map(SubPattern::max_match_len)
+            .try_fold(0, |max, len| len.map(|l| max.max(l)))
+    }
+    pub fn possible_lengths(&self) -> Option<BTreeSet<usize>> {
+        self.0.iter().try_fold(BTreeSet::new(), |mut acc, sp| {
+            acc.extend(sp.possible_lengths()?);
+            Some(acc)
+        })
+    }
+    pub fn branch_widths(&self) -> Vec<Option<usize>> {
+        self.0.iter().map(SubPattern::fixed_width).collect()
+    }
+    /// The common width of all branches, if they share one
+    pub fn fixed_width(&self) -> Option<usize> {
+        let mut widths = self.branch_widths().into_iter();
+        let first = widths.next()??;
+        widths.all(|w| w == Some(first)).then_some(first)
+    }
+    pub(crate) fn walk_paths<'a>(
+        &'a self,
+        path: &mut Vec<usize>,
+        f: &mut impl FnMut(&'a SubPattern, &[usize]),
+    ) {
+        for (ix, sp) in self.0.iter().enumerate() {
+            path.push(ix);
+            sp.walk_paths(path, f);
+            path.pop();
+        }
+    }
     pub(crate) fn groups_count(&self) -> usize {
         self.0.iter().map(SubPattern::groups_count).sum()
     }
-    pub(crate) fn nth_group(&self, n: usize) -> Option<Pattern> {
-        if n == 0 {
-            Some(Pattern::Sub(SubPattern::Alternatives(self.clone())))
-        } else {
-            let mut sps = self.0.iter();
-            while let Some(sp) = sps.next() {
-                let ng = sp.nth_group(n);
-                if ng.is_some() {
-                    return ng;
-                }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::components::Pattern;
+
+    fn first_alternatives(src: &str) -> Alternatives {
+        let p = Pattern::parse(src).unwrap();
+        let mut found = None;
+        p.walk(&mut |sp| {
+            if let SubPattern::Alternatives(alts) = sp
+                && found.is_none()
+            {
+                found = Some(alts.clone());
             }
-            None
-        }
+        });
+        found.unwrap()
+    }
+
+    #[test]
+    fn test_branch_widths() {
+        assert_eq!(
+            vec![Some(2), Some(2)],
+            first_alternatives("(?<=ab|cd)").branch_widths()
+        );
+        assert_eq!(
+            vec![Some(1), Some(2)],
+            first_alternatives("(?<=a|bc)").branch_widths()
+        );
+        assert_eq!(Some(2), first_alternatives("ab|cd").fixed_width());
+        assert_eq!(None, first_alternatives("a|bc").fixed_width());
     }
 }
@@ -0,0 +1,75 @@
+use crate::components::Pattern;
+
+/// A pattern along with metrics computed once up front
+#[derive(Clone, Debug, PartialEq)]
+pub struct AnalyzedPattern {
+    pattern: Pattern,
+    min_match_len: usize,
+    max_match_len: Option<usize>,
+    is_finite: bool,
+    capture_count: usize,
+    groups_count: usize,
+}
+
+impl AnalyzedPattern {
+    pub fn new(pattern: Pattern) -> Self {
+        Self {
+            min_match_len: pattern.min_match_len(),
+            max_match_len: pattern.max_match_len(),
+            is_finite: pattern.is_finite(),
+            capture_count: pattern.capture_count(),
+            groups_count: pattern.groups_count(),
+            pattern,
+        }
+    }
+    pub fn pattern(&self) -> &Pattern {
+        &self.pattern
+    }
+    pub fn into_pattern(self) -> Pattern {
+        self.pattern
+    }
+    pub fn min_match_len(&self) -> usize {
+        self.min_match_len
+    }
+    pub fn max_match_len(&self) -> Option<usize> {
+        self.max_match_len
+    }
+    pub fn is_finite(&self) -> bool {
+        self.is_finite
+    }
+    pub fn capture_count(&self) -> usize {
+        self.capture_count
+    }
+    pub fn groups_count(&self) -> usize {
+        self.groups_count
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_analyzed_matches_direct() {
+        let p = Pattern::parse("(a|bc){2,3}").unwrap();
+        let analyzed = p.clone().analyzed();
+        assert_eq!(p.min_match_len(), analyzed.min_match_len());
+        assert_eq!(p.max_match_len(), analyzed.max_match_len());
+        assert_eq!(Some(6), analyzed.max_match_len());
+        assert_eq!(p.is_finite(), analyzed.is_finite());
+        assert_eq!(p.capture_count(), analyzed.capture_count());
+        assert_eq!(p.groups_count(), analyzed.groups_count());
+        assert_eq!(&p, analyzed.pattern());
+        assert_eq!(None, Pattern::parse("a+b").unwrap().max_match_len());
+    }
+    #[test]
+    fn test_analyzed_quantified_literal_run() {
+        // only the last character of a run is quantified
+        let analyzed = Pattern::parse("ab{2}").unwrap().analyzed();
+        assert_eq!(3, analyzed.min_match_len());
+        assert_eq!(Some(3), analyzed.max_match_len());
+        let analyzed = Pattern::parse("abc?").unwrap().analyzed();
+        assert_eq!(2, analyzed.min_match_len());
+        assert_eq!(Some(3), analyzed.max_match_len());
+    }
+}
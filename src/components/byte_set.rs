@@ -0,0 +1,118 @@
+use crate::{
+    components::{CharSet, Pattern},
+    error::ReggieError,
+    parser::{PyRegexParser, Rule},
+};
+use anyhow::Result;
+use disjoint_ranges::{DisjointRange, UnaryRange};
+use pest::Parser;
+
+/// A pattern read in byte mode, where `\xHH` and set ranges denote bytes
+/// rather than characters
+#[derive(Clone, Debug, PartialEq)]
+pub struct BytePattern {
+    pattern: Pattern,
+}
+
+impl BytePattern {
+    pub fn parse(src: &str) -> Result<Self> {
+        if let Some(c) = src.chars().find(|c| !c.is_ascii()) {
+            return Err(ReggieError::InvalidLiteral {
+                bad_literal: c.into(),
+            }
+            .into());
+        }
+        let src = Pattern::preprocess(src)?;
+        let pair = PyRegexParser::parse(Rule::regex, &src)
+            .map_err(ReggieError::from)?
+            .next()
+            .ok_or(ReggieError::unexpected_eoi(0))?;
+        if let Some(p) = pair
+            .clone()
+            .into_inner()
+            .flatten()
+            .find(|p| p.as_rule() == Rule::unicode_escape)
+        {
+            return Err(ReggieError::InvalidLiteral {
+                bad_literal: p.as_str().into(),
+            }
+            .into());
+        }
+        Ok(Self {
+            pattern: Pattern::from_pair(pair)?,
+        })
+    }
+    pub fn pattern(&self) -> &Pattern {
+        &self.pattern
+    }
+    /// Every character set in the pattern, restricted to bytes
+    pub fn byte_sets(&self) -> Vec<ByteSet> {
+        self.pattern
+            .char_sets()
+            .into_iter()
+            .map(ByteSet::from_char_set)
+            .collect()
+    }
+    pub fn as_string(&self) -> String {
+        self.pattern.as_string()
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct ByteSet {
+    byte_ranges: DisjointRange<u8>,
+}
+
+impl ByteSet {
+    /// Characters up to `\xff` stand for the byte of the same value; anything
+    /// beyond is dropped
+    pub(crate) fn from_char_set(cs: &CharSet) -> Self {
+        let mut byte_ranges = DisjointRange::empty();
+        for range in cs.char_ranges().ranges_iter() {
+            let (low, high) = range.as_bounds();
+            if let Ok(low) = u8::try_from(low) {
+                let high = u8::try_from(high).unwrap_or(u8::MAX);
+                byte_ranges.add_unary_range(UnaryRange::new_unchecked(low, high));
+            }
+        }
+        Self { byte_ranges }
+    }
+    pub fn contains(&self, b: u8) -> bool {
+        self.byte_ranges.contains(b)
+    }
+    pub fn is_full(&self) -> bool {
+        self.byte_ranges
+            .ranges_iter()
+            .eq(DisjointRange::<u8>::entire().ranges_iter())
+    }
+}
+
+impl PartialEq for ByteSet {
+    fn eq(&self, other: &Self) -> bool {
+        self.byte_ranges
+            .ranges_iter()
+            .eq(other.byte_ranges.ranges_iter())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_full_byte_set() {
+        let p = BytePattern::parse("[\\x00-\\xff]").unwrap();
+        let sets = p.byte_sets();
+        assert_eq!(1, sets.len());
+        assert!(sets[0].is_full());
+        let p = BytePattern::parse("[\\x00-\\x7f]").unwrap();
+        assert!(!p.byte_sets()[0].is_full());
+        assert!(!p.byte_sets()[0].contains(0x80));
+    }
+    #[test]
+    fn test_unicode_escape_rejected() {
+        assert!(BytePattern::parse("\\u{100}").is_err());
+        assert!(BytePattern::parse("[a\\u{100}]").is_err());
+        assert!(Pattern::parse("\\u{100}").is_ok());
+    }
+}
@@ -1,53 +1,72 @@
-use crate::{error::ReggieError, parser::Rule};
+use crate::{
+    components::{
+        EmitOptions, Token,
+        json::{Json, from_name, name_of},
+    },
+    error::ReggieError,
+    parser::Rule,
+};
 use anyhow::Result;
 use disjoint_ranges::{DisjointRange, UnaryRange};
 use pest::iterators::Pair;
+use std::hash::{Hash, Hasher};
 #[derive(Clone, Debug)]
 pub struct CharSet {
     char_ranges: DisjointRange<char>,
+    // remembered so `\d` etc. print as written
+    shorthand: Option<CClass>,
+    // the source text, for `EmitOptions::prefer_original`
+    orig: Option<String>,
 }
 
 impl CharSet {
     pub fn from_pair(pair: Pair<Rule>) -> Result<Self> {
         let r = pair.as_rule();
         if let Rule::char_set = r {
+            let orig = Some(String::from(pair.as_str()));
             let mut char_ranges = DisjointRange::empty();
             let mut negated = false;
-            let mut pairs_iter = pair.into_inner();
-            while let Some(p) = pairs_iter.next() {
+            for p in pair.into_inner() {
                 match p.as_rule() {
                     Rule::set_negation => negated = true,
                     Rule::char_range => {
                         let (_, char_ix) = p.line_col();
+                        let range = p.as_str();
                         let mut inner = p.into_inner();
-                        let low = inner
-                            .next()
-                            .ok_or(ReggieError::unexpected_eoi(char_ix))?
-                            .as_str()
-                            .chars()
-                            .nth(0)
-                            .ok_or(ReggieError::unexpected_eoi(char_ix))?;
+                        let low = decode_char(
+                            inner
+                                .next()
+                                .ok_or(ReggieError::unexpected_eoi(char_ix))?
+                                .as_str(),
+                        )?;
                         inner.next();
-                        let high = inner
-                            .next()
-                            .ok_or(ReggieError::unexpected_eoi(char_ix))?
-                            .as_str()
-                            .chars()
-                            .nth(0)
-                            .ok_or(ReggieError::unexpected_eoi(char_ix))?;
+                        let high = decode_char(
+                            inner
+                                .next()
+                                .ok_or(ReggieError::unexpected_eoi(char_ix))?
+                                .as_str(),
+                        )?;
+                        if low > high {
+                            return Err(ReggieError::InvalidRange {
+                                detail: format!("{} runs backwards", range),
+                            }
+                            .into());
+                        }
                         char_ranges.add_unary_range(UnaryRange::new_unchecked(low, high));
                     }
                     Rule::hyphen => {
                         char_ranges.add_unary_range(UnaryRange::new_unchecked('-', '-'))
                     }
                     Rule::set_literal => {
-                        let c = p
-                            .as_str()
-                            .chars()
-                            .nth(0)
-                            .ok_or(ReggieError::unexpected_eoi(p.line_col().1))?;
+                        let c = decode_char(p.as_str())?;
                         char_ranges.add_unary_range(UnaryRange::new_unchecked(c, c));
                     }
+                    Rule::class_range => {
+                        return Err(ReggieError::InvalidRange {
+                            detail: format!("{} uses a character class as an endpoint", p.as_str()),
+                        }
+                        .into());
+                    }
                     Rule::escaped_hyphen => {
                         char_ranges.add_unary_range(UnaryRange::new_unchecked('-', '-'));
                     }
@@ -58,44 +77,305 @@ impl CharSet {
                         let cls = CharClass::from_pair(p)?;
                         char_ranges.add_disjoint_range(cls.to_range());
                     }
+                    Rule::char_set => {
+                        char_ranges.add_disjoint_range(Self::from_pair(p)?.char_ranges);
+                    }
+                    Rule::set_operation => {
+                        let (_, char_ix) = p.line_col();
+                        let mut inner = p.into_inner();
+                        let op = inner.next().ok_or(ReggieError::unexpected_eoi(char_ix))?;
+                        let operand = Self::from_pair(
+                            inner.next().ok_or(ReggieError::unexpected_eoi(char_ix))?,
+                        )?
+                        .char_ranges;
+                        char_ranges = match op.as_rule() {
+                            Rule::set_intersection => intersect_ranges(&char_ranges, &operand),
+                            _ => intersect_ranges(&char_ranges, &operand.complement()),
+                        };
+                    }
                     Rule::l_sq | Rule::r_sq => continue,
                     _ => return Err(ReggieError::unexpected_input(p).into()),
                 };
             }
-            if negated {
-                Ok(Self {
-                    char_ranges: char_ranges.complement(),
-                })
+            let char_ranges = if negated {
+                char_ranges.complement()
             } else {
-                Ok(Self { char_ranges })
-            }
+                char_ranges
+            };
+            Ok(Self {
+                orig,
+                ..Self::new(char_ranges)
+            })
         } else {
             println!("actually {:?}", r);
             unreachable!()
         }
     }
-    pub(crate) fn as_string(&self) -> String {
+    fn new(char_ranges: DisjointRange<char>) -> Self {
+        Self {
+            char_ranges,
+            shorthand: None,
+            orig: None,
+        }
+    }
+    pub(crate) fn from_json_value(json: &Json) -> Result<Self> {
+        let bound = |b: &Json| -> Result<char> {
+            let n = b.as_usize()?;
+            u32::try_from(n)
+                .ok()
+                .and_then(char::from_u32)
+                .ok_or_else(|| ReggieError::invalid_json(format!("bad code point {}", n)).into())
+        };
+        let ranges = json
+            .field("ranges")?
+            .as_array()?
+            .iter()
+            .map(|r| match r.as_array()? {
+                [low, high] => Ok((bound(low)?, bound(high)?)),
+                _ => Err(ReggieError::invalid_json(format!("bad range {}", r)).into()),
+            })
+            .collect::<Result<Vec<(char, char)>>>()?;
+        let mut cs = Self::from_ranges(ranges)?;
+        cs.shorthand = json
+            .opt_field("shorthand")?
+            .map(|cc| from_name(&CClass::JSON_NAMES, cc.as_str()?))
+            .transpose()?;
+        Ok(cs)
+    }
+    pub(crate) fn to_json_value(&self) -> Json {
+        let ranges = self
+            .char_ranges
+            .ranges_iter()
+            .map(|r| {
+                let (low, high) = r.as_bounds();
+                Json::Array(vec![(low as usize).into(), (high as usize).into()])
+            })
+            .collect();
+        Json::tagged(
+            "CharSet",
+            vec![
+                ("ranges", Json::Array(ranges)),
+                (
+                    "shorthand",
+                    Json::opt(self.shorthand.map(|cc| name_of(&CClass::JSON_NAMES, &cc))),
+                ),
+            ],
+        )
+    }
+    pub fn as_string(&self) -> String {
+        self.as_string_with(&EmitOptions::default())
+    }
+    pub fn as_string_with(&self, opts: &EmitOptions) -> String {
+        if opts.prefer_original
+            && let Some(orig) = &self.orig
+        {
+            return orig.clone();
+        }
+        if let Some(cc) = self.shorthand {
+            return cc.as_string();
+        }
         let mut s = String::from("[");
         for subrange in self.char_ranges.ranges_iter() {
             let (low, high) = subrange.as_bounds();
-            s.push_str(format!("{}-{}", low, high).as_str());
+            s.push_str(&range_string_with(low, high, opts));
         }
-        s.push_str("]");
+        s.push(']');
         s
     }
+    pub(crate) fn tokens(&self, out: &mut Vec<Token>) {
+        if let Some(cc) = self.shorthand {
+            out.push(Token::CharClass(cc.as_string()));
+            return;
+        }
+        let opts = EmitOptions::default();
+        out.push(Token::CharSetOpen(String::from("[")));
+        for subrange in self.char_ranges.ranges_iter() {
+            let (low, high) = subrange.as_bounds();
+            out.push(Token::CharSetRange(range_string_with(low, high, &opts)));
+        }
+        out.push(Token::CharSetClose(String::from("]")));
+    }
     pub(crate) fn from_ranges(ranges: Vec<(char, char)>) -> Result<Self> {
-        Ok(Self {
-            char_ranges: DisjointRange::from_bounds(ranges.clone())
+        // from_bounds doesn't sort or merge, so fold into an empty range
+        let mut char_ranges = DisjointRange::empty();
+        char_ranges.add_disjoint_range(
+            DisjointRange::from_bounds(ranges.clone())
                 .ok_or(ReggieError::InvalidRanges { bad_ranges: ranges })?,
-        })
+        );
+        Ok(Self::new(char_ranges))
     }
     pub(crate) fn from_cclass(cclass: CClass) -> Self {
         Self {
             char_ranges: cclass.to_char_class().to_range(),
+            shorthand: Some(cclass),
+            orig: None,
+        }
+    }
+    /// The same characters as plain ranges, forgetting any shorthand or
+    /// source spelling
+    pub fn canonical(&self) -> Self {
+        Self::new(self.char_ranges.clone())
+    }
+    pub fn complement(&self) -> Self {
+        Self {
+            char_ranges: self.char_ranges.clone().complement(),
+            shorthand: self.shorthand.map(CClass::negated),
+            orig: None,
+        }
+    }
+    pub(crate) fn empty() -> Self {
+        Self::new(DisjointRange::empty())
+    }
+    pub(crate) fn entire() -> Self {
+        Self::new(DisjointRange::entire())
+    }
+    pub(crate) fn from_char(c: char) -> Self {
+        Self::new(DisjointRange::new_single_range_unchecked(c, c))
+    }
+    pub fn union(&self, other: &Self) -> Self {
+        let mut char_ranges = self.char_ranges.clone();
+        char_ranges.add_disjoint_range(other.char_ranges.clone());
+        Self::new(char_ranges)
+    }
+    pub(crate) fn char_ranges(&self) -> &DisjointRange<char> {
+        &self.char_ranges
+    }
+    /// How many characters the set contains
+    pub fn cardinality(&self) -> u64 {
+        self.char_ranges
+            .ranges_iter()
+            .map(|r| {
+                let (low, high) = r.as_bounds();
+                u64::from(high) - u64::from(low) + 1
+            })
+            .sum()
+    }
+    pub fn contains(&self, c: char) -> bool {
+        self.char_ranges.contains(c)
+    }
+    /// Whether every ascii letter in the set is accompanied by its other case
+    pub fn is_case_closed(&self) -> bool {
+        ('a'..='z').all(|c| self.contains(c) == self.contains(c.to_ascii_uppercase()))
+    }
+    pub fn is_disjoint(&self, other: &Self) -> bool {
+        intersect_ranges(&self.char_ranges, &other.char_ranges)
+            .ranges_iter()
+            .next()
+            .is_none()
+    }
+    pub fn intersection(&self, other: &Self) -> Self {
+        Self::new(intersect_ranges(&self.char_ranges, &other.char_ranges))
+    }
+    /// Whether every character in `self` is also in `other`
+    pub fn is_subset(&self, other: &Self) -> bool {
+        self.intersection(other) == *self
+    }
+    /// A noun phrase for one character of the set, or several
+    pub(crate) fn explain(&self, plural: bool) -> String {
+        match (self.shorthand.or_else(|| self.as_shorthand()), plural) {
+            (Some(cc), true) => format!("{}s", cc.noun()),
+            (Some(cc), false) => format!("a {}", cc.noun()),
+            (None, true) => format!("characters in {}", self.as_string()),
+            (None, false) => format!("a character in {}", self.as_string()),
+        }
+    }
+    /// The shorthand class with exactly these characters, if any
+    pub fn as_shorthand(&self) -> Option<CClass> {
+        CClass::JSON_NAMES
+            .iter()
+            .map(|(cc, _)| *cc)
+            .find(|cc| Self::from_cclass(*cc) == *self)
+    }
+    pub fn intersects_class(&self, cc: CClass) -> bool {
+        !self.is_disjoint(&Self::from_cclass(cc))
+    }
+}
+
+impl PartialEq for CharSet {
+    fn eq(&self, other: &Self) -> bool {
+        self.char_ranges
+            .ranges_iter()
+            .eq(other.char_ranges.ranges_iter())
+    }
+}
+
+impl Hash for CharSet {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        for range in self.char_ranges.ranges_iter() {
+            range.as_bounds().hash(state);
+        }
+    }
+}
+
+/// A range within a set, or just the character if it's the only one
+fn range_string_with(low: char, high: char, opts: &EmitOptions) -> String {
+    if low == high {
+        escape_set_char_with(low, opts)
+    } else {
+        format!(
+            "{}-{}",
+            escape_set_char_with(low, opts),
+            escape_set_char_with(high, opts)
+        )
+    }
+}
+
+/// Read a single character, either as written or from a `\\xHH`,
+/// `\\u{...}` or backslashed punctuation escape
+pub(crate) fn decode_char(s: &str) -> Result<char> {
+    let hex = s
+        .strip_prefix("\\x")
+        .or_else(|| s.strip_prefix("\\u{").and_then(|h| h.strip_suffix('}')));
+    let c = match hex {
+        Some(h) => u32::from_str_radix(h, 16).ok().and_then(char::from_u32),
+        None => match s.strip_prefix('\\') {
+            Some(escaped) if escaped.len() == 1 => escaped.chars().next(),
+            _ => s.chars().next(),
+        },
+    };
+    c.ok_or(
+        ReggieError::InvalidLiteral {
+            bad_literal: s.into(),
         }
+        .into(),
+    )
+}
+
+/// Write non-ascii characters as escapes the grammar can read back
+pub(crate) fn escape_char_with(c: char, opts: &EmitOptions) -> String {
+    match c as u32 {
+        0x2f if opts.escape_slashes => String::from("\\/"),
+        0..0x80 => c.to_string(),
+        n @ 0x80..0x100 => format!("\\x{:02x}", n),
+        n => format!("\\u{{{:x}}}", n),
     }
 }
 
+/// Like `escape_char_with`, also escaping the characters that would otherwise
+/// end or negate a set, or read as a range
+pub(crate) fn escape_set_char_with(c: char, opts: &EmitOptions) -> String {
+    match c {
+        ']' | '[' | '^' | '-' | '\\' => format!("\\{}", c),
+        c => escape_char_with(c, opts),
+    }
+}
+
+fn intersect_ranges(a: &DisjointRange<char>, b: &DisjointRange<char>) -> DisjointRange<char> {
+    let a: Vec<(char, char)> = a.ranges_iter().map(|r| r.as_bounds()).collect();
+    let b: Vec<(char, char)> = b.ranges_iter().map(|r| r.as_bounds()).collect();
+    let mut out = DisjointRange::empty();
+    let (mut i, mut j) = (0, 0);
+    while i < a.len() && j < b.len() {
+        let low = a[i].0.max(b[j].0);
+        let high = a[i].1.min(b[j].1);
+        if low <= high {
+            out.add_unary_range(UnaryRange::new_unchecked(low, high));
+        }
+        if a[i].1 < b[j].1 { i += 1 } else { j += 1 }
+    }
+    out
+}
+
 #[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub enum CClass {
     D,
@@ -107,6 +387,15 @@ pub enum CClass {
 }
 
 impl CClass {
+    pub(crate) const JSON_NAMES: [(Self, &'static str); 6] = [
+        (Self::D, "D"),
+        (Self::S, "S"),
+        (Self::W, "W"),
+        (Self::NegD, "NegD"),
+        (Self::NegS, "NegS"),
+        (Self::NegW, "NegW"),
+    ];
+    #[allow(clippy::should_implement_trait)]
     pub fn from_str(s: &str) -> Result<Self> {
         match s.strip_prefix("\\").ok_or(ReggieError::InvalidCharClass {
             bad_cclass: String::from(s),
@@ -123,6 +412,37 @@ impl CClass {
             .into()),
         }
     }
+    pub fn negated(self) -> Self {
+        match self {
+            Self::D => Self::NegD,
+            Self::NegD => Self::D,
+            Self::S => Self::NegS,
+            Self::NegS => Self::S,
+            Self::W => Self::NegW,
+            Self::NegW => Self::W,
+        }
+    }
+    pub fn as_string(&self) -> String {
+        let label = match self {
+            Self::D => "d",
+            Self::NegD => "D",
+            Self::S => "s",
+            Self::NegS => "S",
+            Self::W => "w",
+            Self::NegW => "W",
+        };
+        format!("\\{}", label)
+    }
+    fn noun(&self) -> &'static str {
+        match self {
+            Self::D => "digit",
+            Self::NegD => "non-digit",
+            Self::S => "whitespace character",
+            Self::NegS => "non-whitespace character",
+            Self::W => "word character",
+            Self::NegW => "non-word character",
+        }
+    }
     pub(crate) fn to_char_class(self) -> CharClass {
         match self {
             Self::D => CharClass {
@@ -186,30 +506,115 @@ impl CharClass {
         DisjointRange::from_bounds_unchecked([('\t', '\r'), (' ', ' ')])
     }
     fn word_range() -> DisjointRange<char> {
-        DisjointRange::from_bounds_unchecked([('a', 'z'), ('A', 'Z'), ('0', '9')])
+        DisjointRange::from_bounds_unchecked([('0', '9'), ('A', 'Z'), ('a', 'z')])
     }
     pub fn from_pair(pair: Pair<Rule>) -> Result<Self> {
-        let (_, char_ix) = pair.line_col();
-        let mut inner = pair.into_inner();
-        inner.next(); // backslash
-        let c = inner
-            .next()
-            .ok_or(ReggieError::unexpected_eoi(char_ix))?
-            .as_str();
-        Ok(CClass::from_str(c)?.to_char_class())
+        Ok(CClass::from_str(pair.as_str())?.to_char_class())
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::components::Pattern;
 
     #[test]
     fn test_char_set_as_string() {
         let cs = CharSet {
             char_ranges: DisjointRange::from_bounds_unchecked([('a', 'c'), ('e', 'g')]),
+            shorthand: None,
+            orig: None,
         };
         let expected = String::from("[a-ce-g]");
         assert_eq!(expected, cs.as_string())
     }
+    #[test]
+    fn test_singleton_as_string() {
+        assert_eq!(String::from("[a]"), CharSet::from_char('a').as_string());
+        let cs = CharSet::from_ranges(vec![('x', 'z'), ('a', 'a')]).unwrap();
+        assert_eq!(String::from("[ax-z]"), cs.canonical().as_string());
+        let digits = CharSet::from_cclass(CClass::D);
+        assert_eq!(String::from("[0-9]"), digits.canonical().as_string());
+        assert_eq!(digits, digits.canonical());
+    }
+    #[test]
+    fn test_cclass_round_trip() {
+        let p = Pattern::parse("\\d+[a\\s]\\W").unwrap();
+        assert_eq!(String::from("\\d+[\t-\r a]\\W"), p.as_string());
+    }
+    #[test]
+    fn test_escape_round_trip() {
+        let p = Pattern::parse("\\u{100}[\\xe9-\\xff]").unwrap();
+        assert_eq!(String::from("\\u{100}[\\xe9-\\xff]"), p.as_string());
+        assert!(Pattern::parse("\\u{d800}").is_err());
+    }
+    #[test]
+    fn test_escaped_brackets() {
+        let p = Pattern::parse("[a\\]b]").unwrap();
+        let cs = p.char_sets()[0];
+        assert!(cs.contains(']'));
+        assert!(!cs.contains('\\'));
+        assert_eq!(String::from("[\\]a-b]"), p.as_string());
+        assert!(p.verify().is_ok());
+        let p = Pattern::parse("[\\[x\\^]").unwrap();
+        assert!(p.char_sets()[0].contains('['));
+        assert!(p.char_sets()[0].contains('^'));
+        assert!(p.verify().is_ok());
+    }
+    #[test]
+    fn test_class_range_rejected() {
+        let err = Pattern::parse("[\\d-z]").unwrap_err();
+        assert!(matches!(
+            err.downcast_ref::<ReggieError>(),
+            Some(ReggieError::InvalidRange { .. })
+        ));
+        assert!(Pattern::parse("[a-\\w]").is_err());
+        assert!(Pattern::parse("[a-z]").is_ok());
+        assert!(Pattern::parse("[\\w-]").is_ok());
+    }
+    #[test]
+    fn test_descending_range_rejected() {
+        let err = Pattern::parse("[z-a]").unwrap_err();
+        assert!(matches!(
+            err.downcast_ref::<ReggieError>(),
+            Some(ReggieError::InvalidRange { .. })
+        ));
+        assert!(Pattern::parse("[a-a]").is_ok());
+    }
+    #[test]
+    fn test_char_set_intersects_class() {
+        let cs = CharSet::from_ranges(vec![('a', 'z'), ('5', '5')]).unwrap();
+        assert!(cs.intersects_class(CClass::D));
+        let cs = CharSet::from_ranges(vec![('a', 'z')]).unwrap();
+        assert!(!cs.intersects_class(CClass::D));
+    }
+    #[test]
+    fn test_as_shorthand() {
+        let digits = CharSet::from_ranges(vec![('0', '9')]).unwrap();
+        assert_eq!(Some(CClass::D), digits.as_shorthand());
+        assert_eq!(Some(CClass::NegD), digits.complement().as_shorthand());
+        assert_eq!(None, CharSet::from_char('a').as_shorthand());
+    }
+    #[test]
+    fn test_is_subset() {
+        let a_c = CharSet::from_ranges(vec![('a', 'c')]).unwrap();
+        let a_z = CharSet::from_ranges(vec![('a', 'z')]).unwrap();
+        assert!(a_c.is_subset(&a_z));
+        assert!(!a_z.is_subset(&a_c));
+        assert!(a_z.is_subset(&a_z));
+    }
+    #[test]
+    fn test_nested_set_operations() {
+        let p = Pattern::parse("[a-z&&[^aeiou]]").unwrap();
+        let cs = p.char_sets()[0];
+        assert!(cs.contains('b'));
+        assert!(!cs.contains('a'));
+        assert!(!cs.contains('&'));
+        let p = Pattern::parse("[a-f--[c-d]]").unwrap();
+        assert_eq!(String::from("[a-be-f]"), p.as_string());
+        assert_eq!(String::from("[a-f--[c-d]]"), p.as_string_original());
+        let p = Pattern::parse("[a[0-9]]").unwrap();
+        assert!(p.char_sets()[0].contains('5'));
+        assert!(Pattern::parse("[a&b]").unwrap().char_sets()[0].contains('&'));
+    }
 }
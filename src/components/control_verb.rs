@@ -0,0 +1,88 @@
+use crate::{
+    components::json::{Json, from_name, name_of},
+    error::ReggieError,
+    parser::Rule,
+};
+use anyhow::Result;
+use pest::iterators::Pair;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum VerbKind {
+    Accept,
+    Commit,
+    Fail,
+    Prune,
+    Skip,
+    Then,
+}
+
+impl VerbKind {
+    pub(crate) const JSON_NAMES: [(Self, &'static str); 6] = [
+        (Self::Accept, "Accept"),
+        (Self::Commit, "Commit"),
+        (Self::Fail, "Fail"),
+        (Self::Prune, "Prune"),
+        (Self::Skip, "Skip"),
+        (Self::Then, "Then"),
+    ];
+    pub fn from_pair(pair: Pair<Rule>) -> Result<Self> {
+        let (_, char_ix) = pair.line_col();
+        let name = pair
+            .into_inner()
+            .find(|p| p.as_rule() == Rule::verb_name)
+            .ok_or(ReggieError::unexpected_eoi(char_ix))?
+            .as_str();
+        Self::from_name(name)
+    }
+    pub fn from_name(name: &str) -> Result<Self> {
+        match name {
+            "ACCEPT" => Ok(Self::Accept),
+            "COMMIT" => Ok(Self::Commit),
+            "FAIL" | "F" => Ok(Self::Fail),
+            "PRUNE" => Ok(Self::Prune),
+            "SKIP" => Ok(Self::Skip),
+            "THEN" => Ok(Self::Then),
+            _ => Err(ReggieError::InvalidControlVerb {
+                bad_verb: name.into(),
+            }
+            .into()),
+        }
+    }
+    pub(crate) fn from_json_value(json: &Json) -> Result<Self> {
+        from_name(&Self::JSON_NAMES, json.as_str()?)
+    }
+    pub(crate) fn to_json_value(self) -> Json {
+        name_of(&Self::JSON_NAMES, &self).into()
+    }
+    pub fn as_string(&self) -> String {
+        let name = match self {
+            Self::Accept => "ACCEPT",
+            Self::Commit => "COMMIT",
+            Self::Fail => "FAIL",
+            Self::Prune => "PRUNE",
+            Self::Skip => "SKIP",
+            Self::Then => "THEN",
+        };
+        format!("(*{})", name)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::components::Pattern;
+
+    #[test]
+    fn test_control_verb_round_trip() {
+        let p = Pattern::parse("a(*SKIP)(*FAIL)").unwrap();
+        assert_eq!(String::from("a(*SKIP)(*FAIL)"), p.as_string());
+        assert_eq!(1, p.min_match_len());
+        assert_eq!(0, p.groups_count());
+        assert!(Pattern::parse("a(*NOPE)").is_err());
+    }
+    #[test]
+    fn test_control_verb_short_fail() {
+        assert_eq!(VerbKind::Fail, VerbKind::from_name("F").unwrap());
+        assert_eq!(String::from("(*FAIL)"), VerbKind::Fail.as_string());
+    }
+}
@@ -1,10 +1,19 @@
-use crate::{components::char_set::CharSet, error::ReggieError, parser::Rule};
+use crate::{
+    components::{
+        AnchorStyle, EmitOptions, Token,
+        char_set::{CharSet, escape_char_with},
+        json::{Json, from_name, name_of},
+    },
+    error::ReggieError,
+    parser::Rule,
+};
 use anyhow::Result;
 use pest::iterators::Pair;
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, PartialEq, Hash)]
 pub enum Element {
     CharSet(CharSet),
     Literal(Literal),
+    AnyChar,
 }
 
 impl Element {
@@ -24,78 +33,301 @@ impl Element {
     pub(crate) fn new_literal(s: String) -> Self {
         Self::Literal(Literal(s))
     }
+    pub(crate) fn from_json_value(json: &Json) -> Result<Self> {
+        match json.type_tag()? {
+            "CharSet" => Ok(Self::CharSet(CharSet::from_json_value(json)?)),
+            "Literal" => Ok(Self::new_literal(json.field("text")?.as_str()?.into())),
+            "AnyChar" => Ok(Self::AnyChar),
+            other => Err(ReggieError::invalid_json(format!("unexpected type {}", other)).into()),
+        }
+    }
+    pub(crate) fn to_json_value(&self) -> Json {
+        match self {
+            Self::CharSet(cs) => cs.to_json_value(),
+            Self::Literal(l) => Json::tagged("Literal", vec![("text", l.text().into())]),
+            Self::AnyChar => Json::tagged("AnyChar", Vec::new()),
+        }
+    }
     pub fn as_string(&self) -> String {
+        self.as_string_with(&EmitOptions::default())
+    }
+    pub fn as_string_with(&self, opts: &EmitOptions) -> String {
         match self {
-            Self::CharSet(cs) => cs.as_string(),
-            Self::Literal(l) => l.as_string(),
+            Self::CharSet(cs) => cs.as_string_with(opts),
+            Self::Literal(l) => l.as_string_with(opts),
+            Self::AnyChar => String::from("."),
         }
     }
     pub fn min_match_len(&self) -> usize {
         match self {
-            Self::CharSet(_) => 1,
+            Self::CharSet(_) | Self::AnyChar => 1,
             Self::Literal(l) => l.min_match_len(),
         }
     }
+    pub(crate) fn tokens(&self, out: &mut Vec<Token>) {
+        match self {
+            Self::CharSet(cs) => cs.tokens(out),
+            Self::Literal(l) => out.push(Token::Literal(l.as_string())),
+            Self::AnyChar => out.push(Token::Meta(String::from("."))),
+        }
+    }
+    /// A noun phrase for one match of the element, or several
+    pub(crate) fn explain(&self, plural: bool) -> String {
+        match (self, plural) {
+            (Self::CharSet(cs), plural) => cs.explain(plural),
+            (Self::AnyChar, true) => String::from("characters"),
+            (Self::AnyChar, false) => String::from("any character"),
+            (Self::Literal(l), true) => format!("repetitions of {}", l.explain()),
+            (Self::Literal(l), false) => l.explain(),
+        }
+    }
+    pub fn max_match_len(&self) -> usize {
+        match self {
+            Self::CharSet(_) | Self::AnyChar => 1,
+            Self::Literal(l) => l.0.chars().count(),
+        }
+    }
+    pub fn fixed_width(&self) -> Option<usize> {
+        match self {
+            Self::CharSet(_) | Self::AnyChar => Some(1),
+            Self::Literal(l) => Some(l.0.chars().count()),
+        }
+    }
+}
+
+/// A borrowed literal or character set, as found by `Pattern::element_at`
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ElementRef<'a> {
+    Literal(&'a Literal),
+    CharSet(&'a CharSet),
 }
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, PartialEq, Hash)]
 pub struct Literal(String);
 
 impl Literal {
     pub fn from_pair(pair: Pair<Rule>) -> Result<Self> {
         let r = pair.as_rule();
         if let Rule::literals = r {
-            Ok(Self(String::from(pair.as_str())))
+            // the only escapes a run of literals can hold are `\\/`, `\\ ` and `\\#`
+            let mut s = String::new();
+            let mut chars = pair.as_str().chars();
+            while let Some(c) = chars.next() {
+                match c {
+                    '\\' => s.extend(chars.next()),
+                    c => s.push(c),
+                }
+            }
+            Ok(Self(s))
         } else {
             Err(ReggieError::unexpected_input(pair).into())
         }
     }
     pub fn as_string(&self) -> String {
-        self.0.clone()
+        self.as_string_with(&EmitOptions::default())
+    }
+    pub fn as_string_with(&self, opts: &EmitOptions) -> String {
+        self.0
+            .chars()
+            .map(|c| match c {
+                '.' | '^' | '$' | '*' | '+' | '?' | '{' | '}' | '[' | ']' | '\\' | '|' | '('
+                | ')' => format!("\\{}", c),
+                ' ' | '#' if opts.escape_verbose => format!("\\{}", c),
+                c => escape_char_with(c, opts),
+            })
+            .collect()
+    }
+    /// The characters matched, unescaped
+    pub fn text(&self) -> &str {
+        &self.0
+    }
+    pub fn reversed(&self) -> Self {
+        Self(self.0.chars().rev().collect())
+    }
+    fn explain(&self) -> String {
+        if self.0.chars().count() == 1 {
+            format!("'{}'", self.0)
+        } else {
+            format!("the text '{}'", self.0)
+        }
+    }
+    pub(crate) fn first_char(&self) -> Option<char> {
+        self.0.chars().next()
     }
     pub fn min_match_len(&self) -> usize {
-        self.0.len()
+        self.0.chars().count()
     }
 }
 
-#[derive(Clone, Debug, PartialEq)]
+#[derive(Clone, Debug, PartialEq, Hash)]
 pub enum ZeroWidthLiteral {
     InputStart,
+    /// `\\z`, the very end of the input
     InputEnd,
+    /// `\\Z`, the end of the input or just before a newline ending it
+    InputEndBeforeNewline,
     WordBoundary,
     NotWordBoundary,
+    Boundary(BoundaryKind),
+    LineStart,
+    LineEnd,
 }
 
 impl ZeroWidthLiteral {
+    pub(crate) const JSON_NAMES: [(Self, &'static str); 7] = [
+        (Self::InputStart, "InputStart"),
+        (Self::InputEnd, "InputEnd"),
+        (Self::InputEndBeforeNewline, "InputEndBeforeNewline"),
+        (Self::WordBoundary, "WordBoundary"),
+        (Self::NotWordBoundary, "NotWordBoundary"),
+        (Self::LineStart, "LineStart"),
+        (Self::LineEnd, "LineEnd"),
+    ];
     pub fn from_pair(pair: Pair<Rule>) -> Result<Self> {
         let s = pair.as_str();
         match s {
             "\\A" | "\\a" => Ok(Self::InputStart),
             "\\b" => Ok(Self::WordBoundary),
             "\\B" => Ok(Self::NotWordBoundary),
-            "\\Z" | "\\z" => Ok(Self::InputEnd),
-            _ => Err(ReggieError::InvalidLiteral {
-                bad_literal: s.into(),
+            "\\z" => Ok(Self::InputEnd),
+            "\\Z" => Ok(Self::InputEndBeforeNewline),
+            "^" => Ok(Self::LineStart),
+            "$" => Ok(Self::LineEnd),
+            _ => {
+                if let Some(name) = s.strip_prefix("\\b{").and_then(|n| n.strip_suffix('}')) {
+                    Ok(Self::Boundary(BoundaryKind::from_name(name)?))
+                } else {
+                    Err(ReggieError::InvalidLiteral {
+                        bad_literal: s.into(),
+                    }
+                    .into())
+                }
             }
-            .into()),
         }
     }
+    pub(crate) fn from_json_value(json: &Json) -> Result<Self> {
+        match json.field("kind")?.as_str()? {
+            "Boundary" => Ok(Self::Boundary(from_name(
+                &BoundaryKind::JSON_NAMES,
+                json.field("boundary")?.as_str()?,
+            )?)),
+            kind => from_name(&Self::JSON_NAMES, kind),
+        }
+    }
+    pub(crate) fn to_json_value(&self) -> Json {
+        let fields = match self {
+            Self::Boundary(kind) => vec![
+                ("kind", "Boundary".into()),
+                ("boundary", name_of(&BoundaryKind::JSON_NAMES, kind).into()),
+            ],
+            other => vec![("kind", name_of(&Self::JSON_NAMES, other).into())],
+        };
+        Json::tagged("ZeroWidthLiteral", fields)
+    }
     pub fn as_string(&self) -> String {
+        self.as_string_with(&EmitOptions::default())
+    }
+    pub fn as_string_with(&self, opts: &EmitOptions) -> String {
         match self {
-            Self::InputStart => String::from("\\a"),
-            Self::InputEnd => String::from("\\z"),
+            Self::InputStart => match opts.anchors {
+                AnchorStyle::Lowercase => String::from("\\a"),
+                AnchorStyle::Python | AnchorStyle::Perl => String::from("\\A"),
+            },
+            Self::InputEnd => match opts.anchors {
+                AnchorStyle::Lowercase | AnchorStyle::Perl => String::from("\\z"),
+                AnchorStyle::Python => String::from("\\Z"),
+            },
+            // python's `\\Z` is the very end, so spell out the newline
+            Self::InputEndBeforeNewline => match opts.anchors {
+                AnchorStyle::Lowercase | AnchorStyle::Perl => String::from("\\Z"),
+                AnchorStyle::Python => String::from("(?=\\n?\\Z)"),
+            },
             Self::NotWordBoundary => String::from("\\B"),
             Self::WordBoundary => String::from("\\b"),
+            Self::Boundary(kind) => format!("\\b{{{}}}", kind.as_str()),
+            Self::LineStart => String::from("^"),
+            Self::LineEnd => String::from("$"),
         }
     }
     pub fn min_match_len(&self) -> usize {
         0
     }
+    pub(crate) fn explain(&self) -> String {
+        match self {
+            Self::InputStart => String::from("the start of the input"),
+            Self::InputEnd => String::from("the end of the input"),
+            Self::InputEndBeforeNewline => {
+                String::from("the end of the input, or just before a final newline")
+            }
+            Self::WordBoundary => String::from("a word boundary"),
+            Self::NotWordBoundary => String::from("a position that isn't a word boundary"),
+            Self::Boundary(kind) => format!("a {} boundary", kind.noun()),
+            Self::LineStart => String::from("the start of a line"),
+            Self::LineEnd => String::from("the end of a line"),
+        }
+    }
+    /// The anchor matching at the mirrored position when reading right to left
+    pub fn reversed(&self) -> Self {
+        match self {
+            Self::InputStart => Self::InputEnd,
+            Self::InputEnd => Self::InputStart,
+            Self::LineStart => Self::LineEnd,
+            Self::LineEnd => Self::LineStart,
+            other => other.clone(),
+        }
+    }
+}
+
+/// The unicode boundary kinds of `\b{...}`
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum BoundaryKind {
+    Word,
+    Grapheme,
+    Sentence,
+    Line,
+}
+
+impl BoundaryKind {
+    pub(crate) const JSON_NAMES: [(Self, &'static str); 4] = [
+        (Self::Word, "Word"),
+        (Self::Grapheme, "Grapheme"),
+        (Self::Sentence, "Sentence"),
+        (Self::Line, "Line"),
+    ];
+    fn from_name(name: &str) -> Result<Self> {
+        match name {
+            "w" | "wb" | "word" => Ok(Self::Word),
+            "g" | "gcb" => Ok(Self::Grapheme),
+            "sb" => Ok(Self::Sentence),
+            "lb" => Ok(Self::Line),
+            _ => Err(ReggieError::InvalidLiteral {
+                bad_literal: format!("\\b{{{}}}", name),
+            }
+            .into()),
+        }
+    }
+    fn noun(&self) -> &'static str {
+        match self {
+            Self::Word => "word",
+            Self::Grapheme => "grapheme",
+            Self::Sentence => "sentence",
+            Self::Line => "line",
+        }
+    }
+    fn as_str(&self) -> &'static str {
+        match self {
+            Self::Word => "wb",
+            Self::Grapheme => "g",
+            Self::Sentence => "sb",
+            Self::Line => "lb",
+        }
+    }
 }
 
 #[cfg(test)]
 mod test {
     use super::*;
+    use crate::components::Pattern;
 
     #[test]
     fn test_literal_min_match_len() {
@@ -124,4 +356,22 @@ mod test {
             ZeroWidthLiteral::WordBoundary.as_string()
         );
     }
+    #[test]
+    fn test_input_end_round_trip() {
+        let before_newline = Pattern::parse("a\\Z").unwrap();
+        let very_end = Pattern::parse("a\\z").unwrap();
+        assert_ne!(before_newline, very_end);
+        assert_eq!(String::from("a\\Z"), before_newline.as_string());
+        assert_eq!(String::from("a\\z"), very_end.as_string());
+        assert_eq!(
+            before_newline,
+            Pattern::from_json(&before_newline.to_json()).unwrap()
+        );
+    }
+    #[test]
+    fn test_boundary_round_trip() {
+        let p = Pattern::parse("a\\b{g}").unwrap();
+        assert_eq!(String::from("a\\b{g}"), p.as_string());
+        assert!(Pattern::parse("a\\b{nope}").is_err());
+    }
 }
@@ -0,0 +1,59 @@
+/// How `(?P<name>...)` groups are written
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum NamedGroupStyle {
+    /// `(?P<name>...)`
+    #[default]
+    Python,
+    /// `(?<name>...)`
+    Angle,
+    /// `(?'name'...)`
+    Quote,
+}
+
+/// How named backreferences are written
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum BackrefStyle {
+    /// `(?P=name)`
+    #[default]
+    Python,
+    /// `\k<name>`
+    K,
+}
+
+/// How start- and end-of-input anchors are written
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum AnchorStyle {
+    /// `\a` and `\z`
+    #[default]
+    Lowercase,
+    /// `\A` and `\Z`
+    Python,
+    /// `\A` and `\z`
+    Perl,
+}
+
+/// Formatting choices for turning a pattern back into a string. The default
+/// matches `as_string`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct EmitOptions {
+    pub named_groups: NamedGroupStyle,
+    pub backrefs: BackrefStyle,
+    pub anchors: AnchorStyle,
+    /// Write `/` as `\/`, for `/.../`-delimited contexts
+    pub escape_slashes: bool,
+    /// Backslash spaces and `#`s in literals, so the output means the same
+    /// thing in verbose mode
+    pub escape_verbose: bool,
+    /// Write character sets and flags exactly as they were parsed, where the
+    /// source was kept
+    pub prefer_original: bool,
+}
+
+/// Where a string is going to be spliced in, for `reggie::escape_literal`
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum EscapeContext {
+    /// Anywhere outside a character set
+    Pattern,
+    /// Between the brackets of a character set
+    CharSetBody,
+}
@@ -1,28 +1,47 @@
-use crate::{error::ReggieError, parser::Rule};
+use crate::{
+    components::{EmitOptions, Json},
+    error::ReggieError,
+    parser::Rule,
+};
 use anyhow::Result;
 use pest::iterators::Pair;
 use std::{
-    cmp::{Ord, Ordering, PartialOrd},
     collections::BTreeSet,
     fmt::Write,
+    hash::{Hash, Hasher},
 };
 
-#[derive(Clone, Debug, Default, PartialEq)]
+#[derive(Clone, Debug, Default)]
 pub struct Flags {
     pos: BTreeSet<Flag>,
     neg: BTreeSet<Flag>,
+    // the source text, e.g. `?mi`, for `EmitOptions::prefer_original`
+    orig: Option<String>,
+}
+
+impl PartialEq for Flags {
+    fn eq(&self, other: &Self) -> bool {
+        self.pos == other.pos && self.neg == other.neg
+    }
+}
+
+impl Hash for Flags {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.pos.hash(state);
+        self.neg.hash(state);
+    }
 }
 
 impl Flags {
     pub(crate) fn empty() -> Self {
-        Self {
-            pos: BTreeSet::new(),
-            neg: BTreeSet::new(),
-        }
+        Self::default()
     }
     pub fn is_empty(&self) -> bool {
         self.pos.is_empty() && self.neg.is_empty()
     }
+    pub fn contains(&self, f: Flag) -> bool {
+        self.pos.contains(&f)
+    }
     pub(crate) fn has_neg(&self) -> bool {
         !self.neg.is_empty()
     }
@@ -44,7 +63,11 @@ impl Flags {
                 neg.insert(Flag::from_char(c)?);
             }
         };
-        Ok(Self { pos, neg })
+        Ok(Self {
+            pos,
+            neg,
+            orig: Some(format!("?{}", pair.as_str())),
+        })
     }
     pub(crate) fn from_whole_pattern_pair(pair: Pair<Rule>) -> Result<Self> {
         let (_, char_ix) = pair.line_col();
@@ -59,11 +82,32 @@ impl Flags {
             Ok(Self {
                 pos: flags,
                 neg: BTreeSet::new(),
+                orig: Some(format!("?{}", flag_match.as_str())),
             })
         } else {
             Err(ReggieError::unexpected_input(flag_match).into())
         }
     }
+    pub(crate) fn as_string_with(&self, opts: &EmitOptions) -> String {
+        match &self.orig {
+            Some(orig) if opts.prefer_original => orig.clone(),
+            _ => self.as_string(),
+        }
+    }
+    /// How the flags change matching, as phrases to follow an explanation
+    pub(crate) fn explain(&self) -> String {
+        let mut s = String::new();
+        if self.contains(Flag::Ignorecase) {
+            s.push_str(", case-insensitively");
+        }
+        if self.contains(Flag::Multiline) {
+            s.push_str(", with ^ and $ matching at line breaks");
+        }
+        if self.contains(Flag::Dotall) {
+            s.push_str(", with . matching newlines");
+        }
+        s
+    }
     pub(crate) fn as_string(&self) -> String {
         let mut s = format!(
             "?{}",
@@ -79,21 +123,71 @@ impl Flags {
         }
         s
     }
+    pub(crate) fn to_json_value(&self) -> Json {
+        let names =
+            |set: &BTreeSet<Flag>| Json::Array(set.iter().map(|f| f.as_str().into()).collect());
+        Json::Object(vec![
+            (String::from("pos"), names(&self.pos)),
+            (String::from("neg"), names(&self.neg)),
+        ])
+    }
+    pub(crate) fn from_json_value(json: &Json) -> Result<Self> {
+        let flags = |key: &str| -> Result<BTreeSet<Flag>> {
+            json.field(key)?
+                .as_array()?
+                .iter()
+                .map(|f| {
+                    let name = f.as_str()?;
+                    let mut chars = name.chars();
+                    match (chars.next(), chars.next()) {
+                        (Some(c), None) => Flag::from_char(c),
+                        _ => Err(ReggieError::invalid_json(format!("bad flag {}", name)).into()),
+                    }
+                })
+                .collect()
+        };
+        Ok(Self {
+            pos: flags("pos")?,
+            neg: flags("neg")?,
+            orig: None,
+        })
+    }
     pub(crate) fn new_single(f: Flag) -> Self {
         let pos = BTreeSet::from([f]);
         let neg = BTreeSet::new();
-        Self { pos, neg }
+        Self {
+            pos,
+            neg,
+            orig: None,
+        }
     }
     pub fn add_flag(&self, f: Flag) -> Self {
         let mut new = self.clone();
-        new.pos.insert(f);
+        if new.pos.insert(f) {
+            new.orig = None;
+        }
         new
     }
     pub fn remove_flag(&self, f: Flag) -> Self {
         let mut new = self.clone();
-        new.pos.remove(&f);
+        if new.pos.remove(&f) {
+            new.orig = None;
+        }
         new
     }
+    /// The flags in effect inside a group scoped with `scoped`
+    pub(crate) fn apply(&self, scoped: &Self) -> Self {
+        Self {
+            pos: self
+                .pos
+                .union(&scoped.pos)
+                .filter(|f| !scoped.neg.contains(f))
+                .cloned()
+                .collect(),
+            neg: BTreeSet::new(),
+            orig: None,
+        }
+    }
     pub fn combine(&self, other: &Self) -> Self {
         let new_pos: BTreeSet<Flag> = self.pos.union(&other.pos).cloned().collect();
         let new_neg = self.neg.union(&other.neg).cloned().collect();
@@ -101,11 +195,12 @@ impl Flags {
         Self {
             pos: new_pos,
             neg: new_neg,
+            orig: None,
         }
     }
 }
 
-#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
 pub enum Flag {
     Ascii,
     Ignorecase,
@@ -116,34 +211,6 @@ pub enum Flag {
     Verbose,
 }
 
-impl PartialOrd for Flag {
-    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
-        Some(self.cmp(other))
-    }
-}
-
-impl Ord for Flag {
-    fn cmp(&self, other: &Self) -> Ordering {
-        use Flag::*;
-        match (self, other) {
-            (Ascii, Ascii) => Ordering::Equal,
-            (Ascii, _) => Ordering::Greater,
-            (Ignorecase, Ignorecase) => Ordering::Equal,
-            (Ignorecase, _) => Ordering::Greater,
-            (Locale, Locale) => Ordering::Equal,
-            (Locale, _) => Ordering::Equal,
-            (Multiline, Multiline) => Ordering::Equal,
-            (Multiline, _) => Ordering::Equal,
-            (Dotall, Dotall) => Ordering::Equal,
-            (Dotall, _) => Ordering::Equal,
-            (Unicode, Unicode) => Ordering::Equal,
-            (Unicode, _) => Ordering::Equal,
-            (Verbose, Verbose) => Ordering::Equal,
-            (Verbose, _) => Ordering::Equal,
-        }
-    }
-}
-
 impl Flag {
     fn as_str(&self) -> &'static str {
         match self {
@@ -176,14 +243,24 @@ impl Flag {
 #[cfg(test)]
 mod test {
     use super::*;
+    use crate::components::Pattern;
 
     #[test]
     fn test_group_flags_as_string() {
         let flags = Flags {
             pos: BTreeSet::from([Flag::Ignorecase, Flag::Multiline]),
             neg: BTreeSet::from([Flag::Dotall]),
+            orig: None,
         };
         let expected = String::from("?im-s");
         assert_eq!(expected, flags.as_string())
     }
+    #[test]
+    fn test_scoped_negative_flags() {
+        let p = Pattern::parse("(?i-s:a)").unwrap();
+        assert_eq!(String::from("(?i-s:a)"), p.as_string());
+        let p = Pattern::parse("(?-s:a)").unwrap();
+        assert_eq!(String::from("(?-s:a)"), p.as_string());
+        assert!(Pattern::parse("(?-s)a").is_err());
+    }
 }
@@ -1,16 +1,18 @@
 use crate::{
     components::{
+        BackrefStyle, EmitOptions, NamedGroupStyle, Token,
         flags::Flags,
-        pattern::{Pattern, SubPattern},
-        quantified::{Quantifiable, Quantified},
+        json::{Json, from_name, name_of},
+        pattern::SubPattern,
+        quantified::Quantified,
     },
     error::ReggieError,
     parser::Rule,
 };
 use anyhow::Result;
 use pest::iterators::{Pair, Pairs};
-use std::fmt::Write;
-#[derive(Clone, Debug, PartialEq)]
+use std::{collections::BTreeSet, fmt::Write};
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
 pub enum GroupExt {
     NonCapturing,
     Atomic,
@@ -21,6 +23,24 @@ pub enum GroupExt {
 }
 
 impl GroupExt {
+    pub(crate) const JSON_NAMES: [(Self, &'static str); 6] = [
+        (Self::NonCapturing, "NonCapturing"),
+        (Self::Atomic, "Atomic"),
+        (Self::PosLookahead, "PosLookahead"),
+        (Self::NegLookahead, "NegLookahead"),
+        (Self::PosLookbehind, "PosLookbehind"),
+        (Self::NegLookbehind, "NegLookbehind"),
+    ];
+    /// Lookaheads become lookbehinds and vice versa
+    pub fn reversed(&self) -> Self {
+        match self {
+            Self::PosLookahead => Self::PosLookbehind,
+            Self::NegLookahead => Self::NegLookbehind,
+            Self::PosLookbehind => Self::PosLookahead,
+            Self::NegLookbehind => Self::NegLookahead,
+            other => other.clone(),
+        }
+    }
     pub fn as_string(&self) -> String {
         match self {
             Self::NonCapturing => String::from("?:"),
@@ -33,13 +53,26 @@ impl GroupExt {
     }
 }
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, PartialEq, Hash)]
 pub enum TernaryGroupId {
     Numbered(usize),
     Named(String),
 }
 
 impl TernaryGroupId {
+    pub(crate) fn from_json_value(json: &Json) -> Result<Self> {
+        match json {
+            Json::Number(n) => Ok(Self::Numbered(*n)),
+            other => Ok(Self::Named(other.as_str()?.into())),
+        }
+    }
+    /// Numbered ids are written as numbers and named ones as strings
+    pub(crate) fn to_json_value(&self) -> Json {
+        match self {
+            Self::Numbered(n) => (*n).into(),
+            Self::Named(name) => name.as_str().into(),
+        }
+    }
     pub fn as_string(&self) -> String {
         match self {
             TernaryGroupId::Numbered(n) => n.to_string(),
@@ -48,11 +81,15 @@ impl TernaryGroupId {
     }
 }
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, PartialEq, Hash)]
 pub enum Group {
     NamedBackref {
         name: String,
     },
+    /// `\\k<-1>` or `\\k<+1>`, resolved by `Pattern::relative_backref_targets`
+    RelativeBackref {
+        offset: isize,
+    },
     Ternary {
         group_id: TernaryGroupId,
         yes_pat: Box<SubPattern>,
@@ -75,6 +112,13 @@ impl Group {
         match fst.as_rule() {
             Rule::group_ext => Ok(Group::ext_group_from_pairs(fst, inner)?),
             Rule::sub_pattern => Ok(Group::plain_group_from_pairs(fst, inner)?),
+            // `()`
+            Rule::r_parens => Ok(Self::Group {
+                ext: None,
+                flags: Flags::empty(),
+                name: None,
+                components: Vec::new(),
+            }),
             _ => Err(ReggieError::unexpected_input(fst).into()),
         }
     }
@@ -108,10 +152,78 @@ impl Group {
             Rule::neg_lookbehind => Self::neg_lookbehind_group_from_pairs(inner),
             Rule::named_backref => Self::named_backref_from_pairs(ext_pair),
             Rule::named => Self::named_group_from_pairs(ext_pair, inner),
-            Rule::ternary => Self::ternary_group_from_pairs(ext_pair),
+            Rule::ternary => {
+                // the branches take everything up to `)`, so nothing should be left
+                if let Some(extra) = inner.into_iter().find(|p| p.as_rule() != Rule::r_parens) {
+                    return Err(ReggieError::unexpected_input(extra).into());
+                }
+                Self::ternary_group_from_pairs(ext_pair)
+            }
             _ => Err(ReggieError::unexpected_input(ext_pair).into()),
         }
     }
+    pub(crate) fn normalized(&self) -> Self {
+        match self {
+            Self::Group {
+                ext,
+                flags,
+                name,
+                components,
+            } => Self::Group {
+                ext: ext.clone(),
+                flags: flags.clone(),
+                name: name.clone(),
+                components: SubPattern::normalize_sequence(components),
+            },
+            Self::Ternary {
+                group_id,
+                yes_pat,
+                no_pat,
+            } => Self::Ternary {
+                group_id: group_id.clone(),
+                yes_pat: Box::new(yes_pat.normalized()),
+                no_pat: no_pat.as_ref().map(|p| Box::new(p.normalized())),
+            },
+            other => other.clone(),
+        }
+    }
+    pub(crate) fn try_transform(
+        &self,
+        f: &mut impl FnMut(SubPattern) -> Result<SubPattern>,
+    ) -> Result<Self> {
+        Ok(match self {
+            Self::Group {
+                ext,
+                flags,
+                name,
+                components,
+            } => Self::Group {
+                ext: ext.clone(),
+                flags: flags.clone(),
+                name: name.clone(),
+                components: components
+                    .iter()
+                    .map(|c| c.try_transform(f))
+                    .collect::<Result<Vec<SubPattern>>>()?,
+            },
+            Self::Ternary {
+                group_id,
+                yes_pat,
+                no_pat,
+            } => Self::Ternary {
+                group_id: group_id.clone(),
+                yes_pat: Box::new(yes_pat.try_transform(f)?),
+                no_pat: match no_pat {
+                    Some(p) => Some(Box::new(p.try_transform(f)?)),
+                    None => None,
+                },
+            },
+            other => other.clone(),
+        })
+    }
+    pub fn is_capturing(&self) -> bool {
+        matches!(self, Self::Group { ext: None, .. })
+    }
     pub fn name(&self) -> Option<String> {
         if let Group::Group { name, .. } = self {
             name.clone()
@@ -154,7 +266,10 @@ impl Group {
         ext_pair: Pair<Rule>,
         inner: Pairs<'_, Rule>,
     ) -> Result<Self> {
-        let flags = if let Some(matched_flags) = ext_pair.into_inner().next() {
+        let flags = if let Some(matched_flags) = ext_pair
+            .into_inner()
+            .find(|p| p.as_rule() == Rule::scoped_flags)
+        {
             Flags::from_pair(matched_flags)?
         } else {
             Flags::empty()
@@ -186,16 +301,21 @@ impl Group {
         let (_, char_ix) = ext_pair.line_col();
         let name = ext_pair
             .into_inner()
-            .skip(1) // ?
-            .next()
-            .ok_or(ReggieError::unexpected_eoi(char_ix))?
-            .into_inner()
-            .next()
+            .find(|p| p.as_rule() == Rule::pattern_name)
             .ok_or(ReggieError::unexpected_eoi(char_ix))?
             .as_str()
             .into();
         Ok(Self::NamedBackref { name })
     }
+    pub(crate) fn relative_backref_from_pair(pair: Pair<Rule>) -> Result<Self> {
+        let offset = pair
+            .as_str()
+            .strip_prefix("\\k<")
+            .and_then(|n| n.strip_suffix('>'))
+            .and_then(|n| n.parse::<isize>().ok())
+            .ok_or_else(|| ReggieError::unexpected_input(pair))?;
+        Ok(Self::RelativeBackref { offset })
+    }
     fn ternary_group_from_pairs(ext_pair: Pair<Rule>) -> Result<Self> {
         let (_, char_ix) = ext_pair.line_col();
         let mut inner = ext_pair.into_inner();
@@ -203,8 +323,7 @@ impl Group {
             .next()
             .ok_or(ReggieError::unexpected_eoi(char_ix))?
             .into_inner()
-            .skip(1) // (
-            .next()
+            .nth(1) // (
             .ok_or(ReggieError::unexpected_eoi(char_ix))?;
         let group_id = match group.as_rule() {
             Rule::numbered_group_id => TernaryGroupId::Numbered(
@@ -216,35 +335,47 @@ impl Group {
             Rule::named_group_id => TernaryGroupId::Named(group.as_str().into()),
             _ => return Err(ReggieError::unexpected_input(group).into()),
         };
-        if let Some(_) = inner.next() {
-            if let Some(yp_inner) = inner.next() {
-                let yes_pat = Box::new(SubPattern::from_pair(yp_inner)?);
-                // skip |
-                let no_pat = if inner.next().is_some() {
-                    Some(Box::new(SubPattern::from_pair(
-                        inner.next().ok_or(ReggieError::unexpected_eoi(char_ix))?,
-                    )?))
-                } else {
-                    None
-                };
-                Ok(Self::Ternary {
-                    group_id,
-                    yes_pat,
-                    no_pat,
-                })
-            } else {
-                Err(ReggieError::unexpected_eoi(char_ix).into())
-            }
+        let yes_pat = Box::new(Self::ternary_branch_from_pair(
+            inner.next().ok_or(ReggieError::unexpected_eoi(char_ix))?,
+        )?);
+        // skip |
+        let no_pat = if inner.next().is_some() {
+            Some(Box::new(Self::ternary_branch_from_pair(
+                inner.next().ok_or(ReggieError::unexpected_eoi(char_ix))?,
+            )?))
         } else {
-            Err(ReggieError::unexpected_eoi(char_ix).into())
+            None
+        };
+        Ok(Self::Ternary {
+            group_id,
+            yes_pat,
+            no_pat,
+        })
+    }
+    /// One branch of a conditional. A branch of several elements is kept as a
+    /// non-capturing group, so it stays a single sub-pattern
+    fn ternary_branch_from_pair(pair: Pair<Rule>) -> Result<SubPattern> {
+        let mut inner = pair.into_inner();
+        let mut components = Vec::new();
+        while let Some(p) = inner.next() {
+            components.push(SubPattern::single_from_pair(p, &mut inner)?);
+        }
+        if components.len() == 1 {
+            Ok(components.remove(0))
+        } else {
+            Ok(SubPattern::Quantified(Quantified::subpatterns_to_group(
+                components,
+                None,
+                None,
+                Some(GroupExt::NonCapturing),
+            )))
         }
     }
     fn named_group_from_pairs(ext_pair: Pair<Rule>, inner: Pairs<'_, Rule>) -> Result<Self> {
         let (_, char_ix) = ext_pair.line_col();
-        let mut ext_inner = ext_pair.into_inner();
-        ext_inner.next(); // <
-        let name: String = ext_inner
-            .next()
+        let name: String = ext_pair
+            .into_inner()
+            .find(|p| p.as_rule() == Rule::pattern_name)
             .ok_or(ReggieError::unexpected_eoi(char_ix))?
             .as_str()
             .into();
@@ -265,64 +396,175 @@ impl Group {
             flags: Flags::empty(),
         })
     }
-    pub fn as_string(&self) -> String {
+    pub(crate) fn from_json_value(json: &Json) -> Result<Self> {
+        match json.type_tag()? {
+            "NamedBackref" => Ok(Self::NamedBackref {
+                name: json.field("name")?.as_str()?.into(),
+            }),
+            "RelativeBackref" => Ok(Self::RelativeBackref {
+                offset: relative_offset_from_json(json.field("offset")?)?,
+            }),
+            "Ternary" => Ok(Self::Ternary {
+                group_id: TernaryGroupId::from_json_value(json.field("group_id")?)?,
+                yes_pat: Box::new(SubPattern::from_json_value(json.field("yes_pat")?)?),
+                no_pat: json
+                    .opt_field("no_pat")?
+                    .map(|p| SubPattern::from_json_value(p).map(Box::new))
+                    .transpose()?,
+            }),
+            "Group" => Ok(Self::Group {
+                ext: json
+                    .opt_field("ext")?
+                    .map(|e| from_name(&GroupExt::JSON_NAMES, e.as_str()?))
+                    .transpose()?,
+                flags: Flags::from_json_value(json.field("flags")?)?,
+                name: json
+                    .opt_field("name")?
+                    .map(|n| n.as_str().map(String::from))
+                    .transpose()?,
+                components: json
+                    .field("components")?
+                    .as_array()?
+                    .iter()
+                    .map(SubPattern::from_json_value)
+                    .collect::<Result<_>>()?,
+            }),
+            other => Err(ReggieError::invalid_json(format!("unexpected type {}", other)).into()),
+        }
+    }
+    pub(crate) fn to_json_value(&self) -> Json {
+        let components_json =
+            |cs: &[SubPattern]| Json::Array(cs.iter().map(SubPattern::to_json_value).collect());
         match self {
-            Group::NamedBackref { name } => format!("(?P={})", name),
-            Group::Ternary {
-                group_id,
-                yes_pat,
-                no_pat: None,
-            } => format!("(?({}){})", group_id.as_string(), yes_pat.as_string()),
+            Group::NamedBackref { name } => {
+                Json::tagged("NamedBackref", vec![("name", name.as_str().into())])
+            }
+            Group::RelativeBackref { offset } => Json::tagged(
+                "RelativeBackref",
+                vec![("offset", offset.to_string().into())],
+            ),
             Group::Ternary {
                 group_id,
                 yes_pat,
-                no_pat: Some(no_pat),
-            } => format!(
-                "(?({}){}|{})",
-                group_id.as_string(),
-                yes_pat.as_string(),
-                no_pat.as_string()
+                no_pat,
+            } => Json::tagged(
+                "Ternary",
+                vec![
+                    ("group_id", group_id.to_json_value()),
+                    ("yes_pat", yes_pat.to_json_value()),
+                    (
+                        "no_pat",
+                        Json::opt(no_pat.as_ref().map(|p| p.to_json_value())),
+                    ),
+                ],
             ),
             Group::Group {
-                ext: Some(ext),
-                name: None,
-                components: cs,
-                ..
+                ext,
+                flags,
+                name,
+                components,
+            } => Json::tagged(
+                "Group",
+                vec![
+                    (
+                        "ext",
+                        Json::opt(ext.as_ref().map(|e| name_of(&GroupExt::JSON_NAMES, e))),
+                    ),
+                    ("flags", flags.to_json_value()),
+                    ("name", Json::opt(name.as_deref())),
+                    ("components", components_json(components)),
+                ],
+            ),
+        }
+    }
+    pub fn as_string(&self) -> String {
+        self.as_string_with(&EmitOptions::default())
+    }
+    pub fn as_string_with(&self, opts: &EmitOptions) -> String {
+        match self {
+            Group::NamedBackref { name } => match opts.backrefs {
+                BackrefStyle::Python => format!("(?P={})", name),
+                BackrefStyle::K => format!("\\k<{}>", name),
+            },
+            Group::RelativeBackref { offset } => format!("\\k<{:+}>", offset),
+            Group::Ternary {
+                yes_pat, no_pat, ..
             } => {
-                let mut s = format!("(?{}", ext.as_string());
-                for component in cs.iter() {
-                    write!(&mut s, "{}", component.as_string()).unwrap();
+                let mut s = self.opening_with(opts);
+                s.push_str(&yes_pat.as_string_with(opts));
+                if let Some(no_pat) = no_pat {
+                    write!(&mut s, "|{}", no_pat.as_string_with(opts)).unwrap();
                 }
-                write!(&mut s, ")").unwrap();
+                s.push(')');
                 s
             }
+            Group::Group { components, .. } => {
+                let mut s = self.opening_with(opts);
+                for component in components.iter() {
+                    write!(&mut s, "{}", component.as_string_with(opts)).unwrap();
+                }
+                s.push(')');
+                s
+            }
+        }
+    }
+    pub(crate) fn tokens(&self, out: &mut Vec<Token>) {
+        let opening = self.opening_with(&EmitOptions::default());
+        match self {
+            Group::NamedBackref { .. } | Group::RelativeBackref { .. } => {
+                out.push(Token::Meta(opening));
+                return;
+            }
+            Group::Ternary {
+                yes_pat, no_pat, ..
+            } => {
+                out.push(Token::GroupOpen(opening));
+                yes_pat.tokens(out);
+                if let Some(no_pat) = no_pat {
+                    out.push(Token::Alternation(String::from("|")));
+                    no_pat.tokens(out);
+                }
+            }
+            Group::Group { components, .. } => {
+                out.push(Token::GroupOpen(opening));
+                for component in components.iter() {
+                    component.tokens(out);
+                }
+            }
+        }
+        out.push(Token::GroupClose(String::from(")")));
+    }
+    /// Everything up to the group's contents, e.g. `(?P<name>` or `(?(1)`.
+    /// A named backreference has no contents, so this is all of it
+    pub(crate) fn opening_with(&self, opts: &EmitOptions) -> String {
+        match self {
+            Group::NamedBackref { .. } | Group::RelativeBackref { .. } => self.as_string_with(opts),
+            Group::Ternary { group_id, .. } => format!("(?({})", group_id.as_string()),
+            Group::Group {
+                ext: Some(GroupExt::NonCapturing),
+                name: None,
+                flags,
+                ..
+            } => format!("({}:", flags.as_string_with(opts)),
+            Group::Group {
+                ext: Some(ext),
+                name: None,
+                ..
+            } => format!("({}", ext.as_string()),
             Group::Group {
                 ext: None,
                 name: Some(name),
-                components: cs,
                 ..
-            } => {
-                let mut s = format!("(?P<{}>", name);
-                for component in cs.iter() {
-                    write!(&mut s, "{}", component.as_string()).unwrap();
-                }
-                write!(&mut s, ")").unwrap();
-                s
-            }
+            } => match opts.named_groups {
+                NamedGroupStyle::Python => format!("(?P<{}>", name),
+                NamedGroupStyle::Angle => format!("(?<{}>", name),
+                NamedGroupStyle::Quote => format!("(?'{}'", name),
+            },
             Group::Group {
                 ext: None,
                 name: None,
-                components,
                 ..
-            } => {
-                let mut s = String::from("(");
-                for component in components.iter() {
-                    write!(&mut s, "{}", component.as_string()).unwrap();
-                }
-                write!(&mut s, ")").unwrap();
-                s
-            }
-
+            } => String::from("("),
             Group::Group {
                 ext: Some(_),
                 name: Some(_),
@@ -331,12 +573,13 @@ impl Group {
         }
     }
     pub fn is_finite(&self) -> bool {
-        //TODO(shr) similarly flawed
         match self {
-            Group::NamedBackref { .. } => true,
+            // the group referred to is part of the same pattern, so if it's
+            // infinite the pattern already is
+            Group::NamedBackref { .. } | Group::RelativeBackref { .. } => true,
             Group::Ternary {
                 yes_pat, no_pat, ..
-            } => yes_pat.is_finite() && no_pat.as_ref().map_or(true, |p| p.is_finite()),
+            } => yes_pat.is_finite() && no_pat.as_ref().is_none_or(|p| p.is_finite()),
             Group::Group { components, .. } => {
                 for c in components.iter() {
                     if !c.is_finite() {
@@ -350,7 +593,7 @@ impl Group {
     pub fn min_match_len(&self) -> usize {
         //TODO(shr) this isn't quite right
         match self {
-            Group::NamedBackref { .. } => 0,
+            Group::NamedBackref { .. } | Group::RelativeBackref { .. } => 0,
             Group::Ternary { yes_pat, .. } => yes_pat.min_match_len(),
             Group::Group {
                 ext: Some(GroupExt::NonCapturing),
@@ -359,35 +602,114 @@ impl Group {
             Group::Group { components, .. } => components.iter().map(|c| c.min_match_len()).sum(),
         }
     }
-    pub(crate) fn nth_group(&self, mut n: usize) -> Option<Pattern> {
-        if n == 0 {
-            Some(Pattern::Sub(SubPattern::Quantified(Quantified {
-                quantifiable: Quantifiable::Group(self.clone()),
-                quantifier: None,
-            })))
-        } else {
-            match &self {
-                Self::Group {
-                    ext: None,
-                    components,
-                    ..
-                } => {
-                    n -= 1;
-                    let mut cs = components.iter();
-                    while let Some(c) = cs.next() {
-                        let ng = c.nth_group(n);
-                        if ng.is_some() {
-                            return ng;
-                        }
+    /// The longest match, or `None` if unbounded or unknown
+    pub fn max_match_len(&self) -> Option<usize> {
+        match self {
+            Group::NamedBackref { .. } | Group::RelativeBackref { .. } => None,
+            Group::Ternary {
+                yes_pat, no_pat, ..
+            } => {
+                let yes = yes_pat.max_match_len()?;
+                let no = no_pat.as_ref().map_or(Some(0), |p| p.max_match_len())?;
+                Some(yes.max(no))
+            }
+            Group::Group {
+                ext:
+                    Some(
+                        GroupExt::PosLookahead
+                        | GroupExt::NegLookahead
+                        | GroupExt::PosLookbehind
+                        | GroupExt::NegLookbehind,
+                    ),
+                ..
+            } => Some(0),
+            Group::Group { components, .. } => components.iter().map(|c| c.max_match_len()).sum(),
+        }
+    }
+    pub fn possible_lengths(&self) -> Option<BTreeSet<usize>> {
+        match self {
+            Group::NamedBackref { .. } | Group::RelativeBackref { .. } => None,
+            Group::Ternary {
+                yes_pat, no_pat, ..
+            } => {
+                let mut lengths = yes_pat.possible_lengths()?;
+                match no_pat {
+                    Some(no) => lengths.extend(no.possible_lengths()?),
+                    None => {
+                        lengths.insert(0);
                     }
-                    None
                 }
-                _ => None,
+                Some(lengths)
             }
+            Group::Group {
+                ext:
+                    Some(
+                        GroupExt::PosLookahead
+                        | GroupExt::NegLookahead
+                        | GroupExt::PosLookbehind
+                        | GroupExt::NegLookbehind,
+                    ),
+                ..
+            } => Some(BTreeSet::from([0])),
+            Group::Group { components, .. } => SubPattern::sequence_lengths(components),
+        }
+    }
+    /// The number of characters the group always matches, if constant
+    pub fn fixed_width(&self) -> Option<usize> {
+        match self {
+            Group::NamedBackref { .. } | Group::RelativeBackref { .. } => None,
+            Group::Ternary {
+                yes_pat, no_pat, ..
+            } => {
+                let yes = yes_pat.fixed_width()?;
+                let no = no_pat.as_ref().map_or(Some(0), |p| p.fixed_width())?;
+                (yes == no).then_some(yes)
+            }
+            Group::Group {
+                ext:
+                    Some(
+                        GroupExt::PosLookahead
+                        | GroupExt::NegLookahead
+                        | GroupExt::PosLookbehind
+                        | GroupExt::NegLookbehind,
+                    ),
+                ..
+            } => Some(0),
+            Group::Group { components, .. } => components.iter().map(|c| c.fixed_width()).sum(),
+        }
+    }
+    /// The group's components, or a conditional's branches
+    pub(crate) fn children(&self) -> Vec<&SubPattern> {
+        match self {
+            Self::Group { components, .. } => components.iter().collect(),
+            Self::Ternary {
+                yes_pat, no_pat, ..
+            } => std::iter::once(yes_pat.as_ref())
+                .chain(no_pat.as_deref())
+                .collect(),
+            Self::NamedBackref { .. } | Self::RelativeBackref { .. } => Vec::new(),
+        }
+    }
+    pub(crate) fn walk_paths<'a>(
+        &'a self,
+        path: &mut Vec<usize>,
+        f: &mut impl FnMut(&'a SubPattern, &[usize]),
+    ) {
+        for (ix, c) in self.children().into_iter().enumerate() {
+            path.push(ix);
+            c.walk_paths(path, f);
+            path.pop();
         }
     }
 }
 
+/// Offsets are written as strings, since `Json` numbers can't be negative
+fn relative_offset_from_json(json: &Json) -> Result<isize> {
+    let s = json.as_str()?;
+    s.parse()
+        .map_err(|_| ReggieError::invalid_json(format!("bad offset {}", s)).into())
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
@@ -407,12 +729,38 @@ mod test {
             Group::NamedBackref { name: "foo".into() }.as_string()
         );
     }
-    // #[test]
-    // fn test_group_as_string_ternary() {
-    //     todo!()
-    // }
+    #[test]
+    fn test_ternary_branch_sequences() {
+        use crate::components::Pattern;
+        for (src, expected) in [
+            ("(a)(?(1)a[b]|c)", "(a)(?(1)(?:a[b])|c)"),
+            ("(a)(?(1)(x)y|z)", "(a)(?(1)(?:(x)y)|z)"),
+            ("(a)(?(1)x|y\\d+)", "(a)(?(1)x|(?:y\\d+))"),
+        ] {
+            let p = Pattern::parse(src).unwrap();
+            assert_eq!(String::from(expected), p.as_string());
+            assert_eq!(p, Pattern::parse(&p.as_string()).unwrap());
+        }
+        assert_eq!(
+            2,
+            Pattern::parse("(a)(?(1)(x)y|z)").unwrap().capture_count()
+        );
+    }
     // #[test]
     // fn test_group_as_string_group() {
     //     todo!()
     // }
+    #[test]
+    fn test_group_as_string_with_named_style() {
+        use crate::components::{AnchorStyle, Pattern};
+        let p = Pattern::parse("(?P<x>a)(?P=x)\\A").unwrap();
+        assert_eq!(String::from("(?P<x>a)(?P=x)\\a"), p.as_string());
+        let opts = EmitOptions {
+            named_groups: NamedGroupStyle::Angle,
+            backrefs: BackrefStyle::K,
+            anchors: AnchorStyle::Python,
+            ..EmitOptions::default()
+        };
+        assert_eq!(String::from("(?<x>a)\\k<x>\\A"), p.as_string_with(&opts));
+    }
 }
@@ -0,0 +1,362 @@
+use crate::{
+    components::{
+        Pattern,
+        alternatives::Alternatives,
+        element::{Element, ZeroWidthLiteral},
+        flags::{Flag, Flags},
+        groups::{Group, GroupExt},
+        pattern::{Pat, SubPattern},
+        quantified::{Quantifiable, Quantified},
+        quantifiers::{G, Quantifier},
+    },
+    error::ReggieError,
+};
+use anyhow::Result;
+use regex_syntax::hir::{
+    Capture, Class, ClassUnicode, ClassUnicodeRange, Dot, Hir, HirKind, Look, Repetition,
+};
+
+impl Pattern {
+    /// Build a pattern from `regex_syntax`'s intermediate representation.
+    /// Byte classes, non-UTF-8 literals and assertions Python has no spelling
+    /// for aren't supported
+    pub fn from_hir(hir: &Hir) -> Result<Self> {
+        Ok(Self::Pat(Pat::from_sub_patterns(sequence_from_hir(hir)?)))
+    }
+    /// Lower the pattern to `regex_syntax`'s intermediate representation, for
+    /// the `regex` crate to compile. Backreferences, conditionals, lookaround,
+    /// atomic groups, possessive quantifiers and `$` outside multiline mode
+    /// have no equivalent there
+    pub fn to_hir(&self) -> Result<Hir> {
+        let flags = self.flags().unwrap_or(Flags::empty());
+        let mut lowering = Lowering { next_capture: 1 };
+        match self {
+            Self::Pat(Pat { sub_patterns, .. }) => lowering.sequence(sub_patterns, &flags),
+            Self::Sub(sp) => lowering.sub_pattern(sp, &flags),
+        }
+    }
+}
+
+fn unsupported(feature: &str) -> ReggieError {
+    ReggieError::Unsupported {
+        feature: String::from(feature),
+    }
+}
+
+fn non_capturing(components: Vec<SubPattern>, flags: Option<Flags>) -> SubPattern {
+    SubPattern::Quantified(Quantified::subpatterns_to_group(
+        components,
+        flags,
+        None,
+        Some(GroupExt::NonCapturing),
+    ))
+}
+
+fn sequence_from_hir(hir: &Hir) -> Result<Vec<SubPattern>> {
+    match hir.kind() {
+        HirKind::Empty => Ok(Vec::new()),
+        HirKind::Concat(hirs) => hirs.iter().map(sub_pattern_from_hir).collect(),
+        _ => Ok(vec![sub_pattern_from_hir(hir)?]),
+    }
+}
+
+fn sub_pattern_from_hir(hir: &Hir) -> Result<SubPattern> {
+    match hir.kind() {
+        HirKind::Empty | HirKind::Concat(_) => Ok(non_capturing(sequence_from_hir(hir)?, None)),
+        HirKind::Literal(lit) => {
+            let text =
+                std::str::from_utf8(&lit.0).map_err(|_| unsupported("non-UTF-8 literals"))?;
+            Ok(SubPattern::Quantified(Quantified::new_literal(
+                String::from(text),
+                None,
+            )))
+        }
+        HirKind::Class(Class::Unicode(class)) => {
+            let ranges: Vec<(char, char)> = class
+                .ranges()
+                .iter()
+                .map(|r| (r.start(), r.end()))
+                .collect();
+            // `.` without the s flag comes through as everything but `\n`
+            if ranges == [('\0', '\t'), ('\u{b}', char::MAX)] {
+                return Ok(SubPattern::Quantified(Quantified {
+                    quantifiable: Quantifiable::Element(Element::AnyChar),
+                    quantifier: None,
+                }));
+            }
+            Ok(SubPattern::Quantified(
+                Quantified::new_char_set_from_ranges(ranges, None)?,
+            ))
+        }
+        HirKind::Class(Class::Bytes(_)) => Err(unsupported("byte classes").into()),
+        HirKind::Look(look) => zero_width_from_look(*look),
+        HirKind::Repetition(rep) => repetition_from_hir(rep),
+        HirKind::Capture(cap) => Ok(SubPattern::Quantified(Quantified::subpatterns_to_group(
+            sequence_from_hir(&cap.sub)?,
+            None,
+            cap.name.as_deref().map(String::from),
+            None,
+        ))),
+        HirKind::Alternation(hirs) => Ok(SubPattern::Alternatives(Alternatives::from_components(
+            hirs.iter()
+                .map(sub_pattern_from_hir)
+                .collect::<Result<_>>()?,
+        ))),
+    }
+}
+
+fn zero_width_from_look(look: Look) -> Result<SubPattern> {
+    let zwl = |z| SubPattern::ZeroWidthLiteral(z);
+    Ok(match look {
+        Look::Start => zwl(ZeroWidthLiteral::InputStart),
+        Look::End => zwl(ZeroWidthLiteral::InputEnd),
+        Look::WordUnicode => zwl(ZeroWidthLiteral::WordBoundary),
+        Look::WordUnicodeNegate => zwl(ZeroWidthLiteral::NotWordBoundary),
+        // `^` and `$` only mean the ends of lines under the m flag
+        Look::StartLF => non_capturing(
+            vec![zwl(ZeroWidthLiteral::LineStart)],
+            Some(Flags::new_single(Flag::Multiline)),
+        ),
+        Look::EndLF => non_capturing(
+            vec![zwl(ZeroWidthLiteral::LineEnd)],
+            Some(Flags::new_single(Flag::Multiline)),
+        ),
+        other => return Err(unsupported(&format!("look-around assertion {:?}", other)).into()),
+    })
+}
+
+fn repetition_from_hir(rep: &Repetition) -> Result<SubPattern> {
+    let mut quantifier = match (rep.min, rep.max) {
+        (0, Some(1)) => Quantifier::optional(),
+        (0, None) => Quantifier::star(),
+        (1, None) => Quantifier::plus(),
+        (min, None) => Quantifier::at_least(min as usize),
+        (min, Some(max)) if min == max => Quantifier::exactly(min as usize),
+        (min, Some(max)) => Quantifier::between(min as usize, max as usize),
+    };
+    if !rep.greedy {
+        quantifier = quantifier.lazy();
+    }
+    let sub = sub_pattern_from_hir(&rep.sub)?;
+    // a quantifier applies to a single character or group, so anything else
+    // needs wrapping
+    let bare = match &sub {
+        SubPattern::Quantified(q) if q.quantifier.is_none() => match &q.quantifiable {
+            Quantifiable::Element(Element::Literal(lit)) => lit.text().chars().count() == 1,
+            Quantifiable::Element(_) => true,
+            Quantifiable::Group(Group::Group { .. }) => true,
+            Quantifiable::Group(_) => false,
+        },
+        _ => false,
+    };
+    let SubPattern::Quantified(mut q) = (if bare {
+        sub
+    } else {
+        non_capturing(vec![sub], None)
+    }) else {
+        unreachable!()
+    };
+    q.quantifier = Some(quantifier);
+    Ok(SubPattern::Quantified(q))
+}
+
+/// Capture indices are handed out as groups open, the way `regex` numbers them
+struct Lowering {
+    next_capture: u32,
+}
+
+impl Lowering {
+    fn sequence(&mut self, sub_patterns: &[SubPattern], flags: &Flags) -> Result<Hir> {
+        let mut flags = flags.clone();
+        let mut hirs = Vec::new();
+        for sp in sub_patterns {
+            match sp {
+                SubPattern::SetFlags(set) => flags = flags.apply(set),
+                sp => hirs.push(self.sub_pattern(sp, &flags)?),
+            }
+        }
+        Ok(Hir::concat(hirs))
+    }
+    fn sub_pattern(&mut self, sp: &SubPattern, flags: &Flags) -> Result<Hir> {
+        match sp {
+            SubPattern::Alternatives(Alternatives(branches)) => Ok(Hir::alternation(
+                branches
+                    .iter()
+                    .map(|b| self.sub_pattern(b, flags))
+                    .collect::<Result<_>>()?,
+            )),
+            SubPattern::Quantified(q) => self.quantified(q, flags),
+            SubPattern::ZeroWidthLiteral(z) => zero_width_to_hir(z, flags),
+            SubPattern::Comment(_) | SubPattern::SetFlags(_) => Ok(Hir::empty()),
+            SubPattern::Backref(_) => Err(unsupported("backreferences").into()),
+            SubPattern::ControlVerb(_) => Err(unsupported("control verbs").into()),
+            SubPattern::Error { .. } => Err(unsupported("unparsed input").into()),
+        }
+    }
+    fn quantified(&mut self, q: &Quantified, flags: &Flags) -> Result<Hir> {
+        // a quantifier after a run of literals only repeats its last character
+        if let Quantifiable::Element(Element::Literal(lit)) = &q.quantifiable
+            && q.quantifier.is_some()
+            && let Some((ix, _)) = lit.text().char_indices().last()
+            && ix > 0
+        {
+            let (init, last) = lit.text().split_at(ix);
+            return Ok(Hir::concat(vec![
+                element_to_hir(&Element::new_literal(String::from(init)), flags),
+                self.quantified(
+                    &Quantified::new_literal(String::from(last), q.quantifier),
+                    flags,
+                )?,
+            ]));
+        }
+        let sub = match &q.quantifiable {
+            Quantifiable::Element(e) => element_to_hir(e, flags),
+            Quantifiable::Group(g) => self.group(g, flags)?,
+        };
+        let Some(quantifier) = &q.quantifier else {
+            return Ok(sub);
+        };
+        if let G::Possessive = quantifier.greediness() {
+            return Err(unsupported("possessive quantifiers").into());
+        }
+        let count =
+            |n: usize| u32::try_from(n).map_err(|_| unsupported("repetition counts that large"));
+        Ok(Hir::repetition(Repetition {
+            min: count(quantifier.min_len_multiplier())?,
+            max: quantifier.max_count().map(count).transpose()?,
+            greedy: quantifier.is_greedy(),
+            sub: Box::new(sub),
+        }))
+    }
+    fn group(&mut self, group: &Group, flags: &Flags) -> Result<Hir> {
+        match group {
+            Group::Group {
+                ext,
+                flags: scoped,
+                name,
+                components,
+            } => {
+                let flags = flags.apply(scoped);
+                match ext {
+                    None => {
+                        let index = self.next_capture;
+                        self.next_capture += 1;
+                        Ok(Hir::capture(Capture {
+                            index,
+                            name: name.as_deref().map(Box::from),
+                            sub: Box::new(self.sequence(components, &flags)?),
+                        }))
+                    }
+                    Some(GroupExt::NonCapturing) => self.sequence(components, &flags),
+                    Some(GroupExt::Atomic) => Err(unsupported("atomic groups").into()),
+                    Some(_) => Err(unsupported("lookaround").into()),
+                }
+            }
+            Group::Ternary { .. } => Err(unsupported("conditionals").into()),
+            Group::NamedBackref { .. } | Group::RelativeBackref { .. } => {
+                Err(unsupported("backreferences").into())
+            }
+        }
+    }
+}
+
+fn element_to_hir(e: &Element, flags: &Flags) -> Hir {
+    let ignorecase = flags.contains(Flag::Ignorecase);
+    match e {
+        // folded literals become a class per character
+        Element::Literal(lit) if ignorecase => Hir::concat(
+            lit.text()
+                .chars()
+                .map(|c| class_to_hir(vec![ClassUnicodeRange::new(c, c)], true))
+                .collect(),
+        ),
+        Element::Literal(lit) => Hir::literal(lit.text().as_bytes()),
+        Element::CharSet(cs) => class_to_hir(
+            cs.char_ranges()
+                .ranges_iter()
+                .map(|r| {
+                    let (low, high) = r.as_bounds();
+                    ClassUnicodeRange::new(low, high)
+                })
+                .collect(),
+            ignorecase,
+        ),
+        Element::AnyChar if flags.contains(Flag::Dotall) => Hir::dot(Dot::AnyChar),
+        Element::AnyChar => Hir::dot(Dot::AnyCharExceptLF),
+    }
+}
+
+fn class_to_hir(ranges: Vec<ClassUnicodeRange>, ignorecase: bool) -> Hir {
+    let mut class = ClassUnicode::new(ranges);
+    if ignorecase {
+        class.case_fold_simple();
+    }
+    Hir::class(Class::Unicode(class))
+}
+
+fn zero_width_to_hir(z: &ZeroWidthLiteral, flags: &Flags) -> Result<Hir> {
+    let multiline = flags.contains(Flag::Multiline);
+    let look = match z {
+        ZeroWidthLiteral::InputStart => Look::Start,
+        ZeroWidthLiteral::InputEnd => Look::End,
+        ZeroWidthLiteral::WordBoundary => Look::WordUnicode,
+        ZeroWidthLiteral::NotWordBoundary => Look::WordUnicodeNegate,
+        ZeroWidthLiteral::LineStart if multiline => Look::StartLF,
+        ZeroWidthLiteral::LineStart => Look::Start,
+        ZeroWidthLiteral::LineEnd if multiline => Look::EndLF,
+        // these also match just before a final newline, which `regex` can't say
+        ZeroWidthLiteral::LineEnd => return Err(unsupported("`$` outside multiline mode").into()),
+        ZeroWidthLiteral::InputEndBeforeNewline => return Err(unsupported("`\\Z`").into()),
+        ZeroWidthLiteral::Boundary(_) => return Err(unsupported("Unicode boundaries").into()),
+    };
+    Ok(Hir::look(look))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn from_regex_syntax(src: &str) -> Pattern {
+        Pattern::from_hir(&regex_syntax::parse(src).unwrap()).unwrap()
+    }
+    fn compiled(p: &Pattern) -> regex::Regex {
+        regex::Regex::new(&p.to_hir().unwrap().to_string()).unwrap()
+    }
+
+    #[test]
+    fn test_from_hir() {
+        assert_eq!("[a-z]+", from_regex_syntax("[a-z]+").as_string());
+        assert_eq!("(?P<x>ab)*?", from_regex_syntax("(?P<x>ab)*?").as_string());
+        assert_eq!(
+            "(?:ab){2,3}|(?:c.)",
+            from_regex_syntax("(?:ab){2,3}|c.").as_string()
+        );
+        let bytes = regex_syntax::ParserBuilder::new()
+            .utf8(false)
+            .build()
+            .parse("(?-u:[\\x80-\\xFF])")
+            .unwrap();
+        assert!(Pattern::from_hir(&bytes).is_err());
+    }
+    #[test]
+    fn test_to_hir() {
+        let re = compiled(&Pattern::parse("\\d{2,4}").unwrap());
+        assert!(re.is_match("123"));
+        assert!(!re.is_match("1"));
+        let re = compiled(&Pattern::parse("(?i)(a)(?:b(?P<c>c))").unwrap());
+        assert_eq!(3, re.captures_len());
+        assert_eq!("C", &re.captures("ABC").unwrap()["c"]);
+        assert!(Pattern::parse("(a)\\1").unwrap().to_hir().is_err());
+        assert!(Pattern::parse("a$").unwrap().to_hir().is_err());
+    }
+    #[test]
+    fn test_to_hir_quantified_literal_run() {
+        let built = Pattern::new_literal(String::from("ab"), Some(Quantifier::plus()));
+        for p in [Pattern::parse("ab+").unwrap(), built] {
+            let re = compiled(&p);
+            let matches_whole = |s: &str| re.find(s).is_some_and(|m| m.as_str() == s);
+            assert!(matches_whole("abb"));
+            assert!(!matches_whole("abab"));
+        }
+    }
+}
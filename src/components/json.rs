@@ -0,0 +1,292 @@
+use crate::error::ReggieError;
+use anyhow::Result;
+use std::{fmt, iter::Peekable, str::CharIndices};
+
+/// A minimal JSON tree, enough to write out the AST without serde
+#[derive(Clone, Debug, PartialEq)]
+pub enum Json {
+    Null,
+    Number(usize),
+    String(String),
+    Array(Vec<Json>),
+    Object(Vec<(String, Json)>),
+}
+
+impl Json {
+    /// An object whose first field is `"type": tag`
+    pub(crate) fn tagged(tag: &str, fields: Vec<(&str, Json)>) -> Self {
+        let mut obj = vec![(String::from("type"), Self::from(tag))];
+        obj.extend(fields.into_iter().map(|(k, v)| (String::from(k), v)));
+        Self::Object(obj)
+    }
+    pub(crate) fn opt<T: Into<Json>>(val: Option<T>) -> Self {
+        val.map_or(Self::Null, Into::into)
+    }
+    pub fn parse(src: &str) -> Result<Self> {
+        let mut reader = Reader {
+            chars: src.char_indices().peekable(),
+        };
+        let val = reader.value()?;
+        reader.skip_whitespace();
+        match reader.chars.next() {
+            None => Ok(val),
+            Some((ix, c)) => Err(ReggieError::invalid_json(format!(
+                "trailing {:?} at character {}",
+                c, ix
+            ))
+            .into()),
+        }
+    }
+    pub(crate) fn field(&self, key: &str) -> Result<&Self> {
+        match self {
+            Self::Object(fields) => fields
+                .iter()
+                .find(|(k, _)| k == key)
+                .map(|(_, v)| v)
+                .ok_or_else(|| ReggieError::invalid_json(format!("missing field {}", key)).into()),
+            _ => Err(ReggieError::invalid_json(format!("expected an object with {}", key)).into()),
+        }
+    }
+    /// A field that may be `null`
+    pub(crate) fn opt_field(&self, key: &str) -> Result<Option<&Self>> {
+        self.field(key).map(|v| match v {
+            Self::Null => None,
+            v => Some(v),
+        })
+    }
+    pub(crate) fn type_tag(&self) -> Result<&str> {
+        self.field("type")?.as_str()
+    }
+    pub(crate) fn as_str(&self) -> Result<&str> {
+        match self {
+            Self::String(s) => Ok(s),
+            other => {
+                Err(ReggieError::invalid_json(format!("expected a string, got {}", other)).into())
+            }
+        }
+    }
+    pub(crate) fn as_usize(&self) -> Result<usize> {
+        match self {
+            Self::Number(n) => Ok(*n),
+            other => {
+                Err(ReggieError::invalid_json(format!("expected a number, got {}", other)).into())
+            }
+        }
+    }
+    pub(crate) fn as_array(&self) -> Result<&[Self]> {
+        match self {
+            Self::Array(items) => Ok(items),
+            other => {
+                Err(ReggieError::invalid_json(format!("expected an array, got {}", other)).into())
+            }
+        }
+    }
+}
+
+/// The name `val` is written as, from a table of unit variants and names
+pub(crate) fn name_of<T: PartialEq>(table: &[(T, &'static str)], val: &T) -> &'static str {
+    table
+        .iter()
+        .find(|(v, _)| v == val)
+        .map(|(_, name)| *name)
+        .expect("every variant has a JSON name")
+}
+
+/// The unit variant written as `name`
+pub(crate) fn from_name<T: Clone>(table: &[(T, &'static str)], name: &str) -> Result<T> {
+    table
+        .iter()
+        .find(|(_, n)| *n == name)
+        .map(|(v, _)| v.clone())
+        .ok_or_else(|| ReggieError::invalid_json(format!("unknown name {}", name)).into())
+}
+
+struct Reader<'a> {
+    chars: Peekable<CharIndices<'a>>,
+}
+
+impl Reader<'_> {
+    fn skip_whitespace(&mut self) {
+        while self.chars.next_if(|(_, c)| c.is_whitespace()).is_some() {}
+    }
+    fn next(&mut self) -> Result<char> {
+        self.chars
+            .next()
+            .map(|(_, c)| c)
+            .ok_or_else(|| ReggieError::invalid_json("unexpected end of input").into())
+    }
+    fn expect(&mut self, expected: char) -> Result<()> {
+        self.skip_whitespace();
+        match self.next()? {
+            c if c == expected => Ok(()),
+            c => Err(
+                ReggieError::invalid_json(format!("expected {:?}, got {:?}", expected, c)).into(),
+            ),
+        }
+    }
+    /// Consume `close` if it's next, or a comma otherwise
+    fn end_of_item(&mut self, close: char) -> Result<bool> {
+        self.skip_whitespace();
+        if self.chars.next_if(|(_, c)| *c == close).is_some() {
+            Ok(true)
+        } else {
+            self.expect(',')?;
+            Ok(false)
+        }
+    }
+    fn value(&mut self) -> Result<Json> {
+        self.skip_whitespace();
+        match self.chars.peek().map(|(_, c)| *c) {
+            Some('"') => Ok(Json::String(self.string()?)),
+            Some('[') => {
+                self.next()?;
+                let mut items = Vec::new();
+                self.skip_whitespace();
+                if self.chars.next_if(|(_, c)| *c == ']').is_none() {
+                    loop {
+                        items.push(self.value()?);
+                        if self.end_of_item(']')? {
+                            break;
+                        }
+                    }
+                }
+                Ok(Json::Array(items))
+            }
+            Some('{') => {
+                self.next()?;
+                let mut fields = Vec::new();
+                self.skip_whitespace();
+                if self.chars.next_if(|(_, c)| *c == '}').is_none() {
+                    loop {
+                        self.skip_whitespace();
+                        let key = self.string()?;
+                        self.expect(':')?;
+                        fields.push((key, self.value()?));
+                        if self.end_of_item('}')? {
+                            break;
+                        }
+                    }
+                }
+                Ok(Json::Object(fields))
+            }
+            Some('n') => {
+                for expected in "null".chars() {
+                    self.expect(expected)?;
+                }
+                Ok(Json::Null)
+            }
+            Some(c) if c.is_ascii_digit() => {
+                let mut digits = String::new();
+                while let Some((_, d)) = self.chars.next_if(|(_, c)| c.is_ascii_digit()) {
+                    digits.push(d);
+                }
+                digits
+                    .parse()
+                    .map(Json::Number)
+                    .map_err(|_| ReggieError::invalid_json(format!("bad number {}", digits)).into())
+            }
+            Some(c) => Err(ReggieError::invalid_json(format!("unexpected {:?}", c)).into()),
+            None => Err(ReggieError::invalid_json("unexpected end of input").into()),
+        }
+    }
+    fn string(&mut self) -> Result<String> {
+        self.expect('"')?;
+        let mut s = String::new();
+        loop {
+            match self.next()? {
+                '"' => return Ok(s),
+                '\\' => match self.next()? {
+                    'n' => s.push('\n'),
+                    'r' => s.push('\r'),
+                    't' => s.push('\t'),
+                    'u' => {
+                        let hex: String = (0..4).map(|_| self.next()).collect::<Result<_>>()?;
+                        let c = u32::from_str_radix(&hex, 16)
+                            .ok()
+                            .and_then(char::from_u32)
+                            .ok_or_else(|| {
+                                ReggieError::invalid_json(format!("bad escape \\u{}", hex))
+                            })?;
+                        s.push(c);
+                    }
+                    c @ ('"' | '\\' | '/') => s.push(c),
+                    c => {
+                        return Err(ReggieError::invalid_json(format!("bad escape \\{}", c)).into());
+                    }
+                },
+                c => s.push(c),
+            }
+        }
+    }
+}
+
+impl From<&str> for Json {
+    fn from(s: &str) -> Self {
+        Self::String(String::from(s))
+    }
+}
+
+impl From<String> for Json {
+    fn from(s: String) -> Self {
+        Self::String(s)
+    }
+}
+
+impl From<usize> for Json {
+    fn from(n: usize) -> Self {
+        Self::Number(n)
+    }
+}
+
+impl From<Vec<Json>> for Json {
+    fn from(items: Vec<Json>) -> Self {
+        Self::Array(items)
+    }
+}
+
+fn write_string(f: &mut fmt::Formatter<'_>, s: &str) -> fmt::Result {
+    f.write_str("\"")?;
+    for c in s.chars() {
+        match c {
+            '"' => f.write_str("\\\"")?,
+            '\\' => f.write_str("\\\\")?,
+            '\n' => f.write_str("\\n")?,
+            '\r' => f.write_str("\\r")?,
+            '\t' => f.write_str("\\t")?,
+            c if c.is_control() => write!(f, "\\u{:04x}", c as u32)?,
+            c => write!(f, "{}", c)?,
+        }
+    }
+    f.write_str("\"")
+}
+
+impl fmt::Display for Json {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Null => f.write_str("null"),
+            Self::Number(n) => write!(f, "{}", n),
+            Self::String(s) => write_string(f, s),
+            Self::Array(items) => {
+                f.write_str("[")?;
+                for (ix, item) in items.iter().enumerate() {
+                    if ix > 0 {
+                        f.write_str(",")?;
+                    }
+                    write!(f, "{}", item)?;
+                }
+                f.write_str("]")
+            }
+            Self::Object(fields) => {
+                f.write_str("{")?;
+                for (ix, (k, v)) in fields.iter().enumerate() {
+                    if ix > 0 {
+                        f.write_str(",")?;
+                    }
+                    write_string(f, k)?;
+                    write!(f, ":{}", v)?;
+                }
+                f.write_str("}")
+            }
+        }
+    }
+}
@@ -1,26 +1,96 @@
 use crate::{
     components::{
-        CClass, Quantifier,
+        AnalyzedPattern, CClass, CharSet, EmitOptions, Json, Quantifier, Token, VerbKind,
         alternatives::Alternatives,
-        element::ZeroWidthLiteral,
+        element::{Element, ElementRef, ZeroWidthLiteral},
         flags::{Flag, Flags},
-        groups::GroupExt,
-        quantified::Quantified,
+        groups::{Group, GroupExt, TernaryGroupId},
+        quantified::{Quantifiable, Quantified},
     },
     error::ReggieError,
-    parser::Rule,
+    parser::{PyRegexParser, Rule, strip_verbose},
 };
 use anyhow::Result;
-use pest::iterators::{Pair, Pairs};
-use std::fmt::Write;
+use pest::{
+    Parser,
+    iterators::{Pair, Pairs},
+};
+use std::{
+    borrow::Cow,
+    collections::{BTreeMap, BTreeSet, HashSet},
+    fmt::Write,
+    hash::{Hash, Hasher},
+};
 
-#[derive(Clone, Debug)]
+/// Weight of each level of nesting in `complexity_score`
+pub const DEPTH_WEIGHT: u64 = 2;
+/// Weight of each level of nested unbounded repetition, the main driver of
+/// catastrophic backtracking
+pub const STAR_HEIGHT_WEIGHT: u64 = 100;
+/// Weight of each capturing group
+pub const CAPTURE_WEIGHT: u64 = 5;
+/// Character set sizes are summed, capped at this, then divided by
+/// `CARDINALITY_DIVISOR`
+pub const CARDINALITY_CAP: u64 = 65_536;
+pub const CARDINALITY_DIVISOR: u64 = 256;
+/// Quantifier upper bounds are summed, with unbounded quantifiers counting as
+/// this much, and the total capped at it
+pub const QUANTIFIER_MAX_CAP: u64 = 1_000;
+/// `to_alternation_normal_form` leaves patterns that would expand to more
+/// branches than this alone
+pub const MAX_EXPANDED_BRANCHES: usize = 256;
+/// `enumerate` gives up on character sets bigger than this
+pub const MAX_ENUMERATED_CHARS: u64 = 256;
+/// ...and on languages with more strings than this
+pub const MAX_ENUMERATED_STRINGS: usize = 100_000;
+
+#[derive(Clone, Debug, PartialEq, Hash)]
 pub enum Pattern {
     Pat(Pat),
     Sub(SubPattern),
 }
 
 impl Pattern {
+    pub fn parse(src: &str) -> Result<Self> {
+        let src = Self::preprocess(src)?;
+        let pair = PyRegexParser::parse(Rule::regex, &src)
+            .map_err(ReggieError::from)?
+            .next()
+            .ok_or(ReggieError::unexpected_eoi(0))?;
+        Self::from_pair(pair)
+    }
+    /// Like `parse`, calling `on_progress` with the bytes consumed so far and
+    /// the total as each top-level sub-pattern is built. In verbose mode these
+    /// count the source with whitespace and comments stripped
+    pub fn parse_with_progress(
+        src: &str,
+        mut on_progress: impl FnMut(usize, usize),
+    ) -> Result<Self> {
+        let src = Self::preprocess(src)?;
+        let pair = PyRegexParser::parse(Rule::regex, &src)
+            .map_err(ReggieError::from)?
+            .next()
+            .ok_or(ReggieError::unexpected_eoi(0))?;
+        Ok(Self::Pat(Pat::from_pair_with_progress(
+            pair,
+            &mut on_progress,
+        )?))
+    }
+    /// Strip verbose-mode whitespace and comments if the pattern asks for it
+    pub(crate) fn preprocess(src: &str) -> Result<Cow<'_, str>> {
+        let verbose = match PyRegexParser::parse(Rule::whole_pattern_flags, src)
+            .ok()
+            .and_then(|mut pairs| pairs.next())
+        {
+            Some(pair) => Flags::from_whole_pattern_pair(pair)?.contains(Flag::Verbose),
+            None => false,
+        };
+        Ok(if verbose {
+            Cow::Owned(strip_verbose(src))
+        } else {
+            Cow::Borrowed(src)
+        })
+    }
     pub fn from_pair(pair: Pair<Rule>) -> Result<Self> {
         Ok(Self::Pat(Pat::from_pair(pair)?))
     }
@@ -49,11 +119,18 @@ impl Pattern {
     pub fn new_literal(lit: String, quantifier: Option<Quantifier>) -> Self {
         Self::Sub(SubPattern::new_literal(lit, quantifier))
     }
+    /// A pattern matching exactly `text`. Literals hold their text unescaped
+    /// and escape metacharacters when written out, so this is `new_literal`
+    /// under a name that says so
+    pub fn literal_escaped(text: &str, quantifier: Option<Quantifier>) -> Self {
+        Self::new_literal(String::from(text), quantifier)
+    }
     pub fn new_alternatives(components: Vec<Self>) -> Self {
         Self::Sub(SubPattern::new_alternatives(
             components.iter().map(Pattern::into_subpattern).collect(),
         ))
     }
+    #[allow(clippy::wrong_self_convention)]
     pub fn into_group(&self) -> Self {
         match self {
             Self::Pat(Pat {
@@ -69,13 +146,82 @@ impl Pattern {
         }
     }
     pub fn quantify(&self, quantifier: Quantifier) -> Self {
-        if let SubPattern::Quantified(mut q) = self.into_subpattern() {
+        if let Self::Sub(SubPattern::Quantified(mut q)) = self.into_group() {
             q.quantifier = Some(quantifier);
             Self::Sub(SubPattern::Quantified(q))
         } else {
             unreachable!()
         }
     }
+    /// Each branch of a top-level alternation, or just the pattern itself if
+    /// it isn't one. Whole-pattern flags don't belong to any branch, so
+    /// they're left out
+    pub fn alternation_parts(&self) -> Vec<Self> {
+        let sp = match self {
+            Self::Pat(Pat { sub_patterns, .. }) if sub_patterns.len() == 1 => &sub_patterns[0],
+            Self::Sub(sp) => sp,
+            _ => return vec![self.clone()],
+        };
+        match sp {
+            SubPattern::Alternatives(Alternatives(branches)) => {
+                branches.iter().cloned().map(Self::Sub).collect()
+            }
+            _ => vec![self.clone()],
+        }
+    }
+    /// The `alternation_parts` grouped by how many characters they match, or
+    /// `None` if any of them can match more than one length
+    pub fn branches_by_length(&self) -> Option<BTreeMap<usize, Vec<Self>>> {
+        let mut by_length: BTreeMap<usize, Vec<Self>> = BTreeMap::new();
+        for branch in self.alternation_parts() {
+            by_length
+                .entry(branch.fixed_width()?)
+                .or_default()
+                .push(branch);
+        }
+        Some(by_length)
+    }
+    /// Rejoin branches split by `alternation_parts`
+    pub fn from_alternation(mut parts: Vec<Self>) -> Self {
+        if parts.len() == 1 {
+            return parts.remove(0);
+        }
+        Self::Pat(Pat::from_sub_patterns(vec![SubPattern::new_alternatives(
+            parts.iter().map(Self::into_subpattern).collect(),
+        )]))
+    }
+    /// Concatenate two patterns, giving the result both patterns' whole-pattern
+    /// flags. Only one of the `a`, `L` and `u` flags can be in effect, so
+    /// patterns setting different ones can't be joined
+    pub fn concat_preserving_flags(&self, other: &Self) -> Result<Self> {
+        let left = self.flags().unwrap_or(Flags::empty());
+        let right = other.flags().unwrap_or(Flags::empty());
+        let exclusive = |flags: &Flags| {
+            [Flag::Ascii, Flag::Locale, Flag::Unicode]
+                .into_iter()
+                .find(|f| flags.contains(*f))
+        };
+        if let (Some(l), Some(r)) = (exclusive(&left), exclusive(&right))
+            && l != r
+        {
+            return Err(ReggieError::ConflictingFlags {
+                left: left.as_string(),
+                right: right.as_string(),
+            }
+            .into());
+        }
+        let sub_patterns = [self, other]
+            .into_iter()
+            .flat_map(|p| match p {
+                Self::Pat(Pat { sub_patterns, .. }) => sub_patterns.clone(),
+                Self::Sub(sp) => vec![sp.clone()],
+            })
+            .collect();
+        Ok(Self::Pat(Pat {
+            flags: left.combine(&right),
+            sub_patterns,
+        }))
+    }
     pub fn alternate_with(&self, other: &Self) -> Self {
         let l = self.into_subpattern();
         let r = other.into_subpattern();
@@ -124,18 +270,135 @@ impl Pattern {
             Self::Pat(p) => p.without_flag(flag),
         }
     }
+    /// A JSON tree of the AST, each node tagged with its `type`
+    pub fn to_json(&self) -> String {
+        self.to_json_value().to_string()
+    }
+    /// Read back the output of `to_json`
+    pub fn from_json(src: &str) -> Result<Self> {
+        Self::from_json_value(&Json::parse(src)?)
+    }
+    pub(crate) fn from_json_value(json: &Json) -> Result<Self> {
+        match json.type_tag()? {
+            "Pat" => Ok(Self::Pat(Pat::from_json_value(json)?)),
+            "Sub" => Ok(Self::Sub(SubPattern::from_json_value(
+                json.field("sub_pattern")?,
+            )?)),
+            other => Err(ReggieError::invalid_json(format!("unexpected type {}", other)).into()),
+        }
+    }
+    pub(crate) fn to_json_value(&self) -> Json {
+        match self {
+            Self::Pat(p) => p.to_json_value(),
+            Self::Sub(sp) => Json::tagged("Sub", vec![("sub_pattern", sp.to_json_value())]),
+        }
+    }
     pub fn as_string(&self) -> String {
+        self.as_string_with(&EmitOptions::default())
+    }
+    pub fn as_string_with(&self, opts: &EmitOptions) -> String {
         match self {
-            Self::Pat(p) => p.as_string(),
-            Self::Sub(sp) => sp.as_string(),
+            Self::Pat(p) => p.as_string_with(opts),
+            Self::Sub(sp) => sp.as_string_with(opts),
         }
     }
+    /// Like `as_string`, but writing character sets and flags as they
+    /// appeared in the source. Nodes built in code have no source, so fall
+    /// back to the usual output
+    pub fn as_string_original(&self) -> String {
+        self.as_string_with(&original_opts())
+    }
+    /// Like `as_string`, but also escaping literal spaces and `#`s, so the
+    /// output parses back to an equivalent pattern even in verbose mode
+    pub fn to_string_escaped(&self) -> String {
+        self.as_string_with(&EmitOptions {
+            escape_verbose: true,
+            ..EmitOptions::default()
+        })
+    }
+    /// Like `as_string`, but with `/` escaped for `/.../`-delimited contexts
+    pub fn as_delimited_string(&self) -> String {
+        self.as_string_with(&EmitOptions {
+            escape_slashes: true,
+            ..EmitOptions::default()
+        })
+    }
     pub fn min_match_len(&self) -> usize {
         match self {
             Self::Pat(p) => p.min_match_len(),
             Self::Sub(s) => s.min_match_len(),
         }
     }
+    /// The canonical text split into labelled spans; their texts concatenate
+    /// to `as_string`
+    pub fn tokens(&self) -> Vec<Token> {
+        let mut out = Vec::new();
+        match self {
+            Self::Pat(Pat {
+                flags,
+                sub_patterns,
+            }) => {
+                let flags = flags.remove_flag(Flag::Verbose);
+                if !flags.is_empty() {
+                    out.push(Token::Flags(format!("({})", flags.as_string())));
+                }
+                for sp in sub_patterns.iter() {
+                    sp.tokens(&mut out);
+                }
+            }
+            Self::Sub(sp) => sp.tokens(&mut out),
+        }
+        out
+    }
+    /// The longest possible match, or `None` if unbounded. Backreferences
+    /// count as unbounded
+    pub fn max_match_len(&self) -> Option<usize> {
+        match self {
+            Self::Pat(Pat { sub_patterns, .. }) => {
+                sub_patterns.iter().map(SubPattern::max_match_len).sum()
+            }
+            Self::Sub(sp) => sp.max_match_len(),
+        }
+    }
+    /// Every length a match can have, or `None` if there are infinitely many.
+    /// Backreferences count as unbounded
+    pub fn possible_lengths(&self) -> Option<BTreeSet<usize>> {
+        match self {
+            Self::Pat(Pat { sub_patterns, .. }) => SubPattern::sequence_lengths(sub_patterns),
+            Self::Sub(sp) => sp.possible_lengths(),
+        }
+    }
+    /// How many lengths are in `possible_lengths`, if finitely many
+    pub fn distinct_length_count(&self) -> Option<usize> {
+        self.possible_lengths().map(|lengths| lengths.len())
+    }
+    /// The shortest and longest possible matches, the latter `None` if
+    /// unbounded
+    pub fn length_range(&self) -> (usize, Option<usize>) {
+        (self.min_match_len(), self.max_match_len())
+    }
+    /// Whether `n` is within `length_range`
+    pub fn could_match_length(&self, n: usize) -> bool {
+        let (min, max) = self.length_range();
+        n >= min && max.is_none_or(|max| n <= max)
+    }
+    /// Whether every match is empty, as with `(?:)` or `\\A\\z`
+    pub fn matches_only_empty(&self) -> bool {
+        self.max_match_len() == Some(0)
+    }
+    /// Compute the common metrics once, for patterns that are queried often
+    pub fn analyzed(self) -> AnalyzedPattern {
+        AnalyzedPattern::new(self)
+    }
+    /// The number of characters the pattern always matches, if constant
+    pub fn fixed_width(&self) -> Option<usize> {
+        match self {
+            Self::Pat(Pat { sub_patterns, .. }) => {
+                sub_patterns.iter().map(SubPattern::fixed_width).sum()
+            }
+            Self::Sub(sp) => sp.fixed_width(),
+        }
+    }
     pub fn is_finite(&self) -> bool {
         match &self {
             Self::Sub(sp) => sp.is_finite(),
@@ -160,10 +423,100 @@ impl Pattern {
         if n == 0 {
             Some(self.clone())
         } else {
-            match self {
-                Self::Pat(p) => p.nth_group(n),
-                Self::Sub(s) => s.nth_group(n),
-            }
+            let mut groups = Vec::new();
+            self.walk(&mut |sp| {
+                if let Some(g) = sp.group()
+                    && g.is_capturing()
+                {
+                    groups.push(g);
+                }
+            });
+            groups.get(n - 1).map(|g| {
+                Self::Sub(SubPattern::Quantified(Quantified {
+                    quantifiable: Quantifiable::Group((*g).clone()),
+                    quantifier: None,
+                }))
+            })
+        }
+    }
+    /// Complement a pattern consisting of a single (possibly quantified)
+    /// character set
+    pub fn complement_simple(&self) -> Result<Self> {
+        let sp = match self {
+            Self::Pat(Pat { sub_patterns, .. }) if sub_patterns.len() == 1 => &sub_patterns[0],
+            Self::Sub(sp) => sp,
+            _ => return Err(Self::complement_unsupported()),
+        };
+        let SubPattern::Quantified(Quantified {
+            quantifiable: Quantifiable::Element(Element::CharSet(cs)),
+            quantifier,
+        }) = sp
+        else {
+            return Err(Self::complement_unsupported());
+        };
+        let complemented = SubPattern::Quantified(Quantified {
+            quantifiable: Quantifiable::Element(Element::CharSet(cs.complement())),
+            quantifier: *quantifier,
+        });
+        Ok(match self {
+            Self::Pat(Pat { flags, .. }) => Self::Pat(Pat {
+                flags: flags.clone(),
+                sub_patterns: vec![complemented],
+            }),
+            Self::Sub(_) => Self::Sub(complemented),
+        })
+    }
+    fn complement_unsupported() -> anyhow::Error {
+        ReggieError::Unsupported {
+            feature: String::from("complementing anything but a single character set"),
+        }
+        .into()
+    }
+    /// Raise the upper bound of a single-element pattern's quantifier
+    pub fn widen_quantifier(&self, delta_max: usize) -> Self {
+        self.map_single_quantifier(|q| q.widen(delta_max))
+    }
+    /// Lower the lower bound of a single-element pattern's quantifier
+    pub fn narrow_quantifier(&self, delta_min: usize) -> Self {
+        self.map_single_quantifier(|q| q.narrow(delta_min))
+    }
+    fn map_single_quantifier(&self, f: impl Fn(&Quantifier) -> Quantifier) -> Self {
+        let map = |sp: &SubPattern| match sp {
+            SubPattern::Quantified(q) => SubPattern::Quantified(Quantified {
+                quantifiable: q.quantifiable.clone(),
+                quantifier: q.quantifier.as_ref().map(&f),
+            }),
+            other => other.clone(),
+        };
+        match self {
+            Self::Pat(Pat {
+                flags,
+                sub_patterns,
+            }) if sub_patterns.len() == 1 => Self::Pat(Pat {
+                flags: flags.clone(),
+                sub_patterns: vec![map(&sub_patterns[0])],
+            }),
+            Self::Sub(sp) => Self::Sub(map(sp)),
+            _ => self.clone(),
+        }
+    }
+    /// Move whole-pattern flags onto a non-capturing group wrapping the
+    /// entire pattern, e.g. `(?i)abc` to `(?i:abc)`
+    pub fn inline_flags_to_group(&self) -> Self {
+        match self {
+            Self::Pat(Pat {
+                flags,
+                sub_patterns,
+            }) if !flags.is_empty() => Self::Pat(Pat {
+                flags: Flags::empty(),
+                sub_patterns: vec![SubPattern::group_from_subpatterns(
+                    sub_patterns.clone(),
+                    Some(flags.clone()),
+                    None,
+                    Some(GroupExt::NonCapturing),
+                )],
+            }),
+            _ => self.clone(),
         }
     }
     pub fn components(&self) -> Vec<Self> {
@@ -174,234 +527,3710 @@ impl Pattern {
             }
         }
     }
-    fn into_subpattern(&self) -> SubPattern {
-        let Self::Sub(s) = self.into_group() else {
-            unreachable!()
-        };
-        s
+    /// Render for the `regex` crate, which only understands `(?P<name>...)` and
+    /// has no backreferences
+    pub fn to_regex_syntax(&self) -> Result<String> {
+        let mut backref = None;
+        self.walk(&mut |sp| {
+            if let SubPattern::Backref(_) = sp {
+                backref.get_or_insert_with(|| sp.as_string());
+            } else if let Some(g @ (Group::NamedBackref { .. } | Group::RelativeBackref { .. })) =
+                sp.group()
+            {
+                backref.get_or_insert_with(|| g.as_string());
+            }
+        });
+        if let Some(backref) = backref {
+            Err(ReggieError::Unsupported {
+                feature: format!("backreference {}", backref),
+            }
+            .into())
+        } else {
+            Ok(self.as_string())
+        }
     }
-}
-
-#[derive(Clone, Debug)]
-pub struct Pat {
-    flags: Flags,
-    pub sub_patterns: Vec<SubPattern>,
-}
-
-impl Pat {
-    pub fn from_pair(pair: Pair<Rule>) -> Result<Self> {
-        let mut inner = pair.into_inner();
-        let mut flags = Flags::empty();
-        let mut sub_patterns = Vec::new();
-        while let Some(matched) = inner.next() {
-            match matched.as_rule() {
-                Rule::sub_pattern => sub_patterns.push(SubPattern::from_pair(matched)?),
-                Rule::whole_pattern_flags => {
-                    let mut parsed_flags = Flags::from_whole_pattern_pair(matched)?;
-                    std::mem::swap(&mut flags, &mut parsed_flags);
+    /// Check that every backreference and ternary condition refers to a
+    /// capture group that exists somewhere in the pattern
+    pub fn backreference_check(&self) -> Result<()> {
+        let mut targets = self.relative_backref_targets().into_iter();
+        let mut unresolved = None;
+        self.walk(&mut |sp| {
+            if let Some(g @ Group::RelativeBackref { .. }) = sp.group()
+                && targets.next().flatten().is_none()
+            {
+                unresolved.get_or_insert_with(|| g.as_string());
+            }
+        });
+        if let Some(reference) = unresolved {
+            return Err(ReggieError::UndefinedBackreference { reference }.into());
+        }
+        let groups_count = self.groups_count();
+        let numbering = self.group_numbering();
+        for r in self.group_references().into_iter() {
+            let resolves = match &r {
+                TernaryGroupId::Numbered(n) => (1..=groups_count).contains(n),
+                TernaryGroupId::Named(name) => numbering.contains(&Some(name.as_str())),
+            };
+            if !resolves {
+                return Err(ReggieError::UndefinedBackreference {
+                    reference: r.as_string(),
                 }
-                _ => return Err(ReggieError::unexpected_input(matched).into()),
+                .into());
             }
         }
-        Ok(Self {
-            flags,
-            sub_patterns,
-        })
+        Ok(())
     }
-    fn nth_group(&self, n: usize) -> Option<Pattern> {
-        if n == 0 {
-            Some(Pattern::Pat(self.clone()))
-        } else {
-            let i = n;
-            let mut sps = self.sub_patterns.iter();
-            while let Some(sp) = sps.next() {
-                let ng = sp.nth_group(i);
-                if ng.is_some() {
-                    return ng;
+    /// Whether setting `flag` would leave what the pattern matches unchanged
+    pub fn flag_is_noop(&self, flag: Flag) -> bool {
+        if self.flags().is_some_and(|flags| flags.contains(flag)) {
+            return true;
+        }
+        let mut cased_literals = false;
+        let mut case_open_sets = false;
+        let mut char_sets = false;
+        let mut any_char = false;
+        let mut line_anchors = false;
+        let mut word_boundaries = false;
+        let mut backrefs = false;
+        self.walk(&mut |sp| match sp {
+            SubPattern::Quantified(Quantified {
+                quantifiable: Quantifiable::Element(e),
+                ..
+            }) => match e {
+                Element::Literal(l) => {
+                    cased_literals |= l.as_string().chars().any(|c| c.is_alphabetic())
+                }
+                Element::CharSet(cs) => {
+                    char_sets = true;
+                    case_open_sets |= !cs.is_case_closed();
+                }
+                Element::AnyChar => any_char = true,
+            },
+            SubPattern::ZeroWidthLiteral(zwl) => match zwl {
+                ZeroWidthLiteral::LineStart | ZeroWidthLiteral::LineEnd => line_anchors = true,
+                ZeroWidthLiteral::WordBoundary
+                | ZeroWidthLiteral::NotWordBoundary
+                | ZeroWidthLiteral::Boundary(_) => word_boundaries = true,
+                _ => (),
+            },
+            SubPattern::Backref(_) => backrefs = true,
+            _ => {
+                if let Some(Group::NamedBackref { .. } | Group::RelativeBackref { .. }) = sp.group()
+                {
+                    backrefs = true;
                 }
             }
-            None
+        });
+        match flag {
+            Flag::Ignorecase => !cased_literals && !case_open_sets && !backrefs,
+            Flag::Dotall => !any_char,
+            Flag::Multiline => !line_anchors,
+            Flag::Verbose => !self
+                .as_string()
+                .chars()
+                .any(|c| c.is_whitespace() || c == '#'),
+            Flag::Ascii | Flag::Locale | Flag::Unicode => {
+                !char_sets && !word_boundaries && !cased_literals
+            }
         }
     }
-
-    fn as_string(&self) -> String {
-        let mut s = if self.flags.is_empty() {
-            String::new()
-        } else {
-            format!("({})", self.flags.as_string())
+    /// The pattern that matches the reverse of whatever this one matches.
+    /// Backreferences, conditionals and control verbs depend on match order, so
+    /// they aren't supported
+    pub fn reverse(&self) -> Result<Self> {
+        // inline flags cover what follows them, which reversing would move
+        // in front of them, so they're made into scoped groups first
+        let scoped = match self.transform(&mut |sp| match sp {
+            SubPattern::Quantified(Quantified {
+                quantifiable:
+                    Quantifiable::Group(Group::Group {
+                        ext,
+                        flags,
+                        name,
+                        components,
+                    }),
+                quantifier,
+            }) => SubPattern::Quantified(Quantified {
+                quantifiable: Quantifiable::Group(Group::Group {
+                    ext,
+                    flags,
+                    name,
+                    components: SubPattern::scope_inline_flags(components),
+                }),
+                quantifier,
+            }),
+            other => other,
+        }) {
+            Self::Pat(Pat {
+                flags,
+                sub_patterns,
+            }) => Self::Pat(Pat {
+                flags,
+                sub_patterns: SubPattern::scope_inline_flags(sub_patterns),
+            }),
+            sub => sub,
         };
-        for sp in self.sub_patterns.iter() {
-            write!(&mut s, "{}", sp.as_string()).unwrap();
+        let reversed = scoped.try_transform(&mut |sp| match sp {
+            SubPattern::Quantified(Quantified {
+                quantifiable: Quantifiable::Element(Element::Literal(l)),
+                quantifier,
+            }) => Ok(SubPattern::Quantified(Quantified {
+                quantifiable: Quantifiable::Element(Element::Literal(l.reversed())),
+                quantifier,
+            })),
+            SubPattern::Quantified(Quantified {
+                quantifiable:
+                    Quantifiable::Group(Group::Group {
+                        ext,
+                        flags,
+                        name,
+                        mut components,
+                    }),
+                quantifier,
+            }) => {
+                components.reverse();
+                Ok(SubPattern::Quantified(Quantified {
+                    quantifiable: Quantifiable::Group(Group::Group {
+                        ext: ext.map(|e| e.reversed()),
+                        flags,
+                        name,
+                        components,
+                    }),
+                    quantifier,
+                }))
+            }
+            SubPattern::ZeroWidthLiteral(ZeroWidthLiteral::InputEndBeforeNewline) => {
+                Err(Self::reverse_unsupported(&sp))
+            }
+            SubPattern::ZeroWidthLiteral(zwl) => Ok(SubPattern::ZeroWidthLiteral(zwl.reversed())),
+            SubPattern::Backref(_) | SubPattern::ControlVerb(_) => {
+                Err(Self::reverse_unsupported(&sp))
+            }
+            other => match other.group() {
+                Some(
+                    Group::NamedBackref { .. }
+                    | Group::RelativeBackref { .. }
+                    | Group::Ternary { .. },
+                ) => Err(Self::reverse_unsupported(&other)),
+                _ => Ok(other),
+            },
+        })?;
+        Ok(match reversed {
+            Self::Pat(Pat {
+                flags,
+                mut sub_patterns,
+            }) => {
+                sub_patterns.reverse();
+                Self::Pat(Pat {
+                    flags,
+                    sub_patterns,
+                })
+            }
+            sub => sub,
+        })
+    }
+    fn reverse_unsupported(sp: &SubPattern) -> anyhow::Error {
+        ReggieError::Unsupported {
+            feature: format!("reversing {}", sp.as_string()),
         }
-        s
+        .into()
     }
-    fn is_finite(&self) -> bool {
-        for sp in self.sub_patterns.iter() {
-            if !sp.is_finite() {
-                return false;
+    /// The name of each capturing group, if any, in numbering order, so group
+    /// `n` is at index `n - 1`
+    pub(crate) fn group_numbering(&self) -> Vec<Option<&str>> {
+        let mut numbering = Vec::new();
+        self.walk(&mut |sp| {
+            if let Some(g) = sp.group().filter(|g| g.is_capturing()) {
+                numbering.push(match g {
+                    Group::Group { name, .. } => name.as_deref(),
+                    _ => None,
+                });
+            }
+        });
+        numbering
+    }
+    /// Each named group with its name, in the order the groups are defined
+    pub fn named_groups_ordered(&self) -> Vec<(String, &Group)> {
+        let mut named = Vec::new();
+        self.walk(&mut |sp| {
+            if let Some(
+                g @ Group::Group {
+                    name: Some(name), ..
+                },
+            ) = sp.group()
+            {
+                named.push((name.clone(), g));
             }
+        });
+        named
+    }
+    /// Check that every group name, and every name referred to, is an
+    /// identifier as engines expect: `[A-Za-z_][A-Za-z0-9_]*`
+    pub fn validate_group_names(&self) -> Result<()> {
+        let is_identifier = |name: &str| {
+            name.starts_with(|c: char| c.is_ascii_alphabetic() || c == '_')
+                && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+        };
+        let mut names: Vec<String> = self
+            .group_numbering()
+            .into_iter()
+            .flatten()
+            .map(String::from)
+            .collect();
+        names.extend(self.group_references().into_iter().filter_map(|r| match r {
+            TernaryGroupId::Named(name) => Some(name),
+            TernaryGroupId::Numbered(_) => None,
+        }));
+        match names.into_iter().find(|name| !is_identifier(name)) {
+            Some(name) => Err(ReggieError::InvalidGroupName { name }.into()),
+            None => Ok(()),
         }
-        true
     }
-    fn min_match_len(&self) -> usize {
-        self.sub_patterns.iter().map(|sp| sp.min_match_len()).sum()
+    /// The group each relative backreference refers to, in document order,
+    /// or `None` if it points before the first group or past the last. `-1`
+    /// is the group most recently opened before the reference, `+1` the next
+    /// one opened after it
+    pub fn relative_backref_targets(&self) -> Vec<Option<usize>> {
+        let groups_count = self.groups_count();
+        let mut opened = 0usize;
+        let mut targets = Vec::new();
+        self.walk(&mut |sp| match sp.group() {
+            Some(g) if g.is_capturing() => opened += 1,
+            Some(Group::RelativeBackref { offset }) => {
+                let target = match offset.signum() {
+                    -1 => opened.checked_sub(offset.unsigned_abs() - 1),
+                    1 => Some(opened + offset.unsigned_abs()),
+                    _ => None,
+                };
+                targets.push(target.filter(|n| (1..=groups_count).contains(n)));
+            }
+            _ => (),
+        });
+        targets
     }
-    fn without_flag(&self, flag: Flag) -> Pattern {
-        let mut new = self.clone();
-        let new_flags = new.flags.remove_flag(flag);
-        new.flags = new_flags;
-        Pattern::Pat(new)
+    /// Every group referred to by a backreference or ternary condition.
+    /// Relative backreferences are included by the number they resolve to
+    pub(crate) fn group_references(&self) -> Vec<TernaryGroupId> {
+        let mut relative = self.relative_backref_targets().into_iter().flatten();
+        let mut refs = Vec::new();
+        self.walk(&mut |sp| match sp {
+            SubPattern::Backref(n) => refs.push(TernaryGroupId::Numbered(*n)),
+            _ => match sp.group() {
+                Some(Group::NamedBackref { name }) => {
+                    refs.push(TernaryGroupId::Named(name.clone()))
+                }
+                Some(Group::Ternary { group_id, .. }) => refs.push(group_id.clone()),
+                Some(Group::RelativeBackref { .. }) => {
+                    refs.extend(relative.next().map(TernaryGroupId::Numbered))
+                }
+                _ => (),
+            },
+        });
+        refs
     }
-}
-
-#[derive(Clone, Debug)]
-pub enum SubPattern {
-    Alternatives(Alternatives),
-    Quantified(Quantified),
-    ZeroWidthLiteral(ZeroWidthLiteral),
-    Comment(String),
-}
-
-impl SubPattern {
-    pub fn from_pair(pair: Pair<Rule>) -> Result<Self> {
-        let (_, char_ix) = pair.line_col();
-        let mut inner = pair.into_inner();
-        if let Some(p) = inner.next() {
-            SubPattern::single_from_pair(p, &mut inner)
-        } else {
-            Err(ReggieError::unexpected_eoi(char_ix).into())
+    /// Capturing groups nothing refers back to, by number
+    pub fn unused_captures(&self) -> Vec<usize> {
+        let refs = self.group_references();
+        self.group_numbering()
+            .into_iter()
+            .zip(1..)
+            .filter(|(name, n)| {
+                !refs.iter().any(|r| match r {
+                    TernaryGroupId::Numbered(m) => m == n,
+                    TernaryGroupId::Named(r_name) => *name == Some(r_name.as_str()),
+                })
+            })
+            .map(|(_, n)| n)
+            .collect()
+    }
+    /// Rebuild with every capturing group passed through `f` along with its
+    /// number
+    fn map_captures(&self, f: &mut impl FnMut(usize, Quantified) -> Quantified) -> Self {
+        let mut next = 1;
+        match self {
+            Self::Pat(Pat {
+                flags,
+                sub_patterns,
+            }) => Self::Pat(Pat {
+                flags: flags.clone(),
+                sub_patterns: sub_patterns
+                    .iter()
+                    .map(|sp| sp.map_captures(&mut next, f))
+                    .collect(),
+            }),
+            Self::Sub(sp) => Self::Sub(sp.map_captures(&mut next, f)),
         }
     }
-    pub fn single_from_pair(pair: Pair<Rule>, inner: &mut Pairs<'_, Rule>) -> Result<Self> {
-        match pair.as_rule() {
-            Rule::alternatives => SubPattern::alternatives_from_pair(pair),
-            Rule::group | Rule::literals | Rule::char_set => {
-                SubPattern::quantified_from_pair(pair, inner)
+    /// Put `q` on capturing group `group_number`, replacing any quantifier
+    /// it already has
+    pub fn quantify_group(&self, group_number: usize, q: Quantifier) -> Result<Self> {
+        if group_number == 0 || group_number > self.group_numbering().len() {
+            return Err(ReggieError::NoSuchGroup {
+                number: group_number,
             }
-            Rule::zero_width_literal => SubPattern::zwl_from_pair(pair),
-            Rule::comment_group => SubPattern::comment_group_from_pair(pair),
-            _ => {
-                println!("single_from_pair actually {:?}", pair.as_rule());
-                Err(ReggieError::unexpected_input(pair).into())
+            .into());
+        }
+        Ok(self.map_captures(&mut |n, quantified| {
+            if n == group_number {
+                Quantified {
+                    quantifier: Some(q),
+                    ..quantified
+                }
+            } else {
+                quantified
             }
+        }))
+    }
+    /// Turn every capturing group nothing refers back to into a non-capturing
+    /// one, renumbering the references to those that remain
+    pub fn minimize_captures(&self) -> Self {
+        // dropping a capture would change what a relative reference points to
+        if !self.relative_backref_targets().is_empty() {
+            return self.clone();
         }
+        let unused = self.unused_captures();
+        let renumber = |n: usize| n - unused.iter().filter(|u| **u < n).count();
+        let uncaptured = self.map_captures(&mut |n, q| match q.quantifiable {
+            Quantifiable::Group(Group::Group {
+                flags, components, ..
+            }) if unused.contains(&n) => Quantified {
+                quantifiable: Quantifiable::Group(Group::Group {
+                    ext: Some(GroupExt::NonCapturing),
+                    flags,
+                    name: None,
+                    components,
+                }),
+                quantifier: q.quantifier,
+            },
+            _ => q,
+        });
+        uncaptured.transform(&mut |sp| match sp {
+            SubPattern::Backref(n) => SubPattern::Backref(renumber(n)),
+            SubPattern::Quantified(Quantified {
+                quantifiable:
+                    Quantifiable::Group(Group::Ternary {
+                        group_id: TernaryGroupId::Numbered(n),
+                        yes_pat,
+                        no_pat,
+                    }),
+                quantifier,
+            }) => SubPattern::Quantified(Quantified {
+                quantifiable: Quantifiable::Group(Group::Ternary {
+                    group_id: TernaryGroupId::Numbered(renumber(n)),
+                    yes_pat,
+                    no_pat,
+                }),
+                quantifier,
+            }),
+            other => other,
+        })
     }
-    fn group_from_subpatterns(
-        components: Vec<Self>,
-        flags: Option<Flags>,
-        name: Option<String>,
-        ext: Option<GroupExt>,
-    ) -> Self {
-        Self::Quantified(Quantified::subpatterns_to_group(
-            components, flags, name, ext,
-        ))
+    /// Every kind of group extension used anywhere in the pattern
+    pub fn group_ext_kinds(&self) -> HashSet<GroupExt> {
+        let mut kinds = HashSet::new();
+        self.walk(&mut |sp| {
+            if let Some(Group::Group { ext: Some(ext), .. }) = sp.group() {
+                kinds.insert(ext.clone());
+            }
+        });
+        kinds
     }
-    fn new_alternatives(components: Vec<SubPattern>) -> Self {
-        Self::Alternatives(Alternatives::from_components(components))
+    pub fn ternary_condition_refs(&self) -> Vec<TernaryGroupId> {
+        let mut refs = Vec::new();
+        self.walk(&mut |sp| {
+            if let Some(Group::Ternary { group_id, .. }) = sp.group() {
+                refs.push(group_id.clone());
+            }
+        });
+        refs
     }
-    fn new_char_set(ranges: Vec<(char, char)>, quantifier: Option<Quantifier>) -> Result<Self> {
-        Ok(Self::Quantified(Quantified::new_char_set_from_ranges(
-            ranges, quantifier,
-        )?))
+    /// The pattern with every quantifier dropped, so each element matches
+    /// exactly once, and adjacent literals merged
+    pub fn skeleton(&self) -> Self {
+        let stripped = self.transform(&mut |sp| match sp {
+            SubPattern::Quantified(Quantified { quantifiable, .. }) => {
+                SubPattern::Quantified(Quantified {
+                    quantifiable,
+                    quantifier: None,
+                })
+            }
+            other => other,
+        });
+        Self::Pat(stripped.normalized())
     }
-    fn new_char_class(cc: CClass, quantifier: Option<Quantifier>) -> Self {
-        Self::Quantified(Quantified::new_char_class(cc, quantifier))
+    /// Rewrite brace quantifiers with a shorthand equivalent, e.g. `{0,}` to
+    /// `*`, dropping `{1}` altogether
+    pub fn normalize_quantifier_spelling(&self) -> Self {
+        self.transform(&mut |sp| match sp {
+            SubPattern::Quantified(Quantified {
+                quantifiable,
+                quantifier: Some(q),
+            }) => SubPattern::Quantified(Quantified {
+                quantifiable,
+                quantifier: q.normalized(),
+            }),
+            other => other,
+        })
     }
-    fn new_literal(lit: String, quantifier: Option<Quantifier>) -> Self {
-        Self::Quantified(Quantified::new_literal(lit, quantifier))
+    /// The longest chain of nested sub-patterns
+    pub fn depth(&self) -> usize {
+        let mut depth = 0;
+        self.walk_paths(&mut Vec::new(), &mut |_, path| {
+            depth = depth.max(path.len())
+        });
+        depth
     }
-    pub fn as_group(&self) -> Self {
-        Self::Quantified(Quantified::subpatterns_to_group(
-            vec![self.clone()],
-            None,
-            None,
-            None,
-        ))
+    /// How many AST nodes make up the pattern: the pattern itself, each
+    /// sub-pattern, and the element, group or alternatives each of those
+    /// wraps, so `a(b|c)` has 11
+    pub fn node_count(&self) -> usize {
+        let mut count = 1;
+        self.walk(&mut |sp| {
+            count += match sp {
+                SubPattern::Alternatives(_) | SubPattern::Quantified(_) => 2,
+                _ => 1,
+            }
+        });
+        count
     }
-    pub fn flags(&self) -> Flags {
+    /// A plain-English description, e.g. "between 2 and 4 digits" for
+    /// `\\d{2,4}`
+    pub fn explain(&self) -> String {
+        let mut next = 1;
+        match self {
+            Self::Pat(Pat {
+                flags,
+                sub_patterns,
+            }) => format!(
+                "{}{}",
+                SubPattern::explain_sequence(sub_patterns, &mut next),
+                flags.explain()
+            ),
+            Self::Sub(sp) => sp.explain(&mut next),
+        }
+    }
+    /// The text the pattern matches if it's just one literal, give or take
+    /// surrounding anchors and flags that don't change how literals match
+    pub fn as_plain_literal(&self) -> Option<String> {
+        let Self::Pat(Pat {
+            flags,
+            sub_patterns,
+        }) = self
+        else {
+            return Pattern::Pat(self.normalized()).as_plain_literal();
+        };
+        // word boundaries depend on the text around a match, so a literal
+        // wrapped in them won't match wherever the bare text turns up
+        let is_edge = |sp: &SubPattern| match sp {
+            SubPattern::ZeroWidthLiteral(
+                ZeroWidthLiteral::WordBoundary
+                | ZeroWidthLiteral::NotWordBoundary
+                | ZeroWidthLiteral::Boundary(_),
+            ) => false,
+            SubPattern::ZeroWidthLiteral(_) | SubPattern::Comment(_) => true,
+            _ => false,
+        };
+        let start = sub_patterns.iter().position(|sp| !is_edge(sp))?;
+        let end = sub_patterns.iter().rposition(|sp| !is_edge(sp))?;
+        let merged = SubPattern::normalize_sequence(&sub_patterns[start..=end]);
+        let [literal] = merged.as_slice() else {
+            return None;
+        };
+        let text = literal.bare_literal()?;
+        if flags.contains(Flag::Ignorecase) && text.chars().any(char::is_alphabetic) {
+            None
+        } else {
+            Some(text)
+        }
+    }
+    /// Literals every match contains: those reached through the main
+    /// concatenation without passing an alternation, a lookaround or a
+    /// quantifier that allows zero repetitions
+    pub fn required_literals(&self) -> Vec<String> {
+        let mut out = Vec::new();
+        match self {
+            Self::Pat(Pat { sub_patterns, .. }) => {
+                for sp in sub_patterns.iter() {
+                    sp.required_literals(&mut out);
+                }
+            }
+            Self::Sub(sp) => sp.required_literals(&mut out),
+        }
+        out
+    }
+    /// Whether every match has to start at offset 0, because it begins with
+    /// `\\A`, or with `^` while multiline mode is off
+    pub fn must_match_at_start(&self) -> bool {
+        match self {
+            Self::Pat(Pat {
+                flags,
+                sub_patterns,
+            }) => SubPattern::sequence_must_match_at_start(
+                sub_patterns,
+                flags.contains(Flag::Multiline),
+            ),
+            Self::Sub(sp) => sp.must_match_at_start(false),
+        }
+    }
+    /// Whether every match ends at `\\z`, `\\Z` or `$`, ignoring trailing comments
+    pub fn is_end_anchored(&self) -> bool {
+        match self {
+            Self::Pat(Pat { sub_patterns, .. }) => {
+                SubPattern::sequence_is_end_anchored(sub_patterns)
+            }
+            Self::Sub(sp) => sp.is_end_anchored(),
+        }
+    }
+    /// Distribute concatenation over alternation, so `(a|b)c` becomes
+    /// `ac|bc`. Only unquantified groups without flags or extensions other
+    /// than `?:` are opened up, which drops their captures, so patterns that
+    /// refer back to a group are returned unchanged, as are those that would
+    /// expand past `MAX_EXPANDED_BRANCHES`
+    pub fn to_alternation_normal_form(&self) -> Self {
+        if !self.group_references().is_empty() {
+            return self.clone();
+        }
+        let (flags, sub_patterns) = match self {
+            Self::Pat(Pat {
+                flags,
+                sub_patterns,
+            }) => (flags.clone(), sub_patterns.clone()),
+            Self::Sub(sp) => (Flags::empty(), vec![sp.clone()]),
+        };
+        let Some(branches) = SubPattern::sequence_branches(&sub_patterns) else {
+            return self.clone();
+        };
+        let parts = branches
+            .iter()
+            .map(|branch| {
+                Self::Pat(Pat {
+                    flags: Flags::empty(),
+                    sub_patterns: SubPattern::normalize_sequence(branch),
+                })
+            })
+            .collect();
+        match Self::from_alternation(parts) {
+            Self::Pat(Pat { sub_patterns, .. }) => Self::Pat(Pat {
+                flags,
+                sub_patterns,
+            }),
+            Self::Sub(sp) => Self::Pat(Pat {
+                flags,
+                sub_patterns: vec![sp],
+            }),
+        }
+    }
+    /// Pull the longest run of elements every top-level branch starts with out
+    /// of the alternation, so `abc|abd` becomes `ab(?:c|d)`. A branch that's
+    /// all prefix is left as an empty group, as in `foo(?:(?:)|t)`
+    pub fn factor_common_prefix(&self) -> Self {
+        self.factor_common_end(false)
+    }
+    /// Like `factor_common_prefix`, but for the run every branch ends with, so
+    /// `cat|bat` becomes `(?:c|b)at`
+    pub fn factor_common_suffix(&self) -> Self {
+        self.factor_common_end(true)
+    }
+    fn factor_common_end(&self, suffix: bool) -> Self {
+        let parts = self.alternation_parts();
+        if parts.len() < 2 {
+            return self.clone();
+        }
+        let branches: Vec<Vec<SubPattern>> = parts
+            .iter()
+            .map(|part| {
+                let mut units = SubPattern::factoring_units(&part.into_subpattern());
+                if suffix {
+                    units.reverse();
+                }
+                units
+            })
+            .collect();
+        let common = branches
+            .iter()
+            .map(|branch| {
+                branch
+                    .iter()
+                    .zip(branches[0].iter())
+                    .take_while(|(a, b)| a == b)
+                    .count()
+            })
+            .min()
+            .unwrap_or(0);
+        if common == 0 {
+            return self.clone();
+        }
+        let in_order = |units: &[SubPattern]| {
+            let mut units = units.to_vec();
+            if suffix {
+                units.reverse();
+            }
+            SubPattern::normalize_sequence(&units)
+        };
+        let rests = branches
+            .iter()
+            .map(|branch| match in_order(&branch[common..]).as_slice() {
+                [single] => single.clone(),
+                rest => SubPattern::group_from_subpatterns(
+                    rest.to_vec(),
+                    None,
+                    None,
+                    Some(GroupExt::NonCapturing),
+                ),
+            })
+            .collect();
+        let factored = SubPattern::group_from_subpatterns(
+            vec![SubPattern::new_alternatives(rests)],
+            None,
+            None,
+            Some(GroupExt::NonCapturing),
+        );
+        let shared = in_order(&branches[0][..common]);
+        let sub_patterns = if suffix {
+            std::iter::once(factored).chain(shared).collect()
+        } else {
+            shared
+                .into_iter()
+                .chain(std::iter::once(factored))
+                .collect()
+        };
+        Self::Pat(Pat {
+            flags: self.flags().unwrap_or(Flags::empty()),
+            sub_patterns,
+        })
+    }
+    /// Merge runs of the same unquantified element into one repeated element,
+    /// so `[a-z][a-z][a-z]` becomes `[a-z]{3}`
+    pub fn collapse_repeated_elements(&self) -> Self {
+        let collapsed = self.transform(&mut |sp| match sp {
+            SubPattern::Quantified(Quantified {
+                quantifiable:
+                    Quantifiable::Group(Group::Group {
+                        ext,
+                        flags,
+                        name,
+                        components,
+                    }),
+                quantifier,
+            }) => SubPattern::Quantified(Quantified {
+                quantifiable: Quantifiable::Group(Group::Group {
+                    ext,
+                    flags,
+                    name,
+                    components: SubPattern::collapse_runs(components),
+                }),
+                quantifier,
+            }),
+            other => other,
+        });
+        match collapsed {
+            Self::Pat(Pat {
+                flags,
+                sub_patterns,
+            }) => Self::Pat(Pat {
+                flags,
+                sub_patterns: SubPattern::collapse_runs(sub_patterns),
+            }),
+            sub => sub,
+        }
+    }
+    /// Replace every unbounded lazy quantifier with a greedy one allowing at
+    /// most `cap` repetitions (or its minimum, if that's higher), for engines
+    /// that handle bounded repetition better. This is lossy: `a*?` becomes
+    /// `a{0,3}` with a `cap` of 3, which prefers longer matches and gives up
+    /// on longer runs
+    pub fn bound_lazy_quantifiers(&self, cap: usize) -> Self {
+        self.transform(&mut |sp| match sp {
+            SubPattern::Quantified(Quantified {
+                quantifiable,
+                quantifier: Some(q),
+            }) if !q.is_greedy() && !q.is_finite() => {
+                let min = q.min_len_multiplier();
+                SubPattern::Quantified(Quantified {
+                    quantifiable,
+                    quantifier: Some(Quantifier::between(min, cap.max(min))),
+                })
+            }
+            other => other,
+        })
+    }
+    /// Write every character set that matches a shorthand class as that
+    /// class, e.g. `[^0-9]` as `\\D`
+    pub fn prefer_shorthands(&self) -> Self {
+        self.transform(&mut |sp| match sp {
+            SubPattern::Quantified(Quantified {
+                quantifiable: Quantifiable::Element(Element::CharSet(cs)),
+                quantifier,
+            }) => SubPattern::Quantified(Quantified {
+                quantifiable: Quantifiable::Element(Element::CharSet(
+                    cs.as_shorthand().map_or(cs, CharSet::from_cclass),
+                )),
+                quantifier,
+            }),
+            other => other,
+        })
+    }
+    /// A hash of the normalized structure that's the same from run to run, for
+    /// bucketing patterns before comparing them in full. Character sets hash
+    /// by contents, so `[a-c]`, `[abc]` and `[a-bc]` agree. The hasher is a
+    /// fixed FNV-1a, so values only change if std's `Hash` impls for strings,
+    /// integers and collections do
+    pub fn content_hash(&self) -> u64 {
+        let mut hasher = Fnv1a::default();
+        self.normalized().hash(&mut hasher);
+        hasher.finish()
+    }
+    /// Every string of at most `max_len` characters the pattern matches, in
+    /// order. Backreferences, lookaround, anchors, verbs and case-insensitivity
+    /// aren't supported, nor are character sets larger than
+    /// `MAX_ENUMERATED_CHARS` or results larger than `MAX_ENUMERATED_STRINGS`
+    pub fn enumerate(&self, max_len: usize) -> Result<Vec<String>> {
+        let strings = match self {
+            Self::Pat(Pat {
+                flags,
+                sub_patterns,
+            }) => {
+                if flags.contains(Flag::Ignorecase) {
+                    return Err(ReggieError::Unsupported {
+                        feature: String::from("enumerating case-insensitive patterns"),
+                    }
+                    .into());
+                }
+                SubPattern::enumerate_sequence(sub_patterns, max_len)?
+            }
+            Self::Sub(sp) => sp.enumerate(max_len)?,
+        };
+        Ok(strings.into_iter().collect())
+    }
+    /// Whether the pattern clearly matches every string, empty or not, as
+    /// `(?s).*` or `[\\s\\S]*` do. Conservative: `false` just means it isn't
+    /// obvious. `.` only counts as any character where the `s` flag is in
+    /// effect, since otherwise it skips newlines
+    pub fn matches_everything(&self) -> bool {
+        match self {
+            Self::Pat(Pat {
+                flags,
+                sub_patterns,
+            }) => SubPattern::sequence_matches_everything(sub_patterns, flags),
+            Self::Sub(sp) => sp.matches_everything(&Flags::empty()),
+        }
+    }
+    /// Whether `other` starts with the same elements as `self`, comparing
+    /// literals character by character, so `ab` is a prefix of `abc`. This is
+    /// structural, not a comparison of the languages matched
+    pub fn is_prefix_of(&self, other: &Self) -> bool {
+        let units = |p: &Self| -> Vec<SubPattern> {
+            let Pat { sub_patterns, .. } = p.normalized();
+            sub_patterns
+                .iter()
+                .flat_map(SubPattern::factoring_units)
+                .collect()
+        };
+        let (ours, theirs) = (units(self), units(other));
+        self.normalized().flags == other.normalized().flags && theirs.starts_with(&ours)
+    }
+    /// The deepest nesting of unbounded quantifiers
+    pub fn star_height(&self) -> usize {
+        match self {
+            Self::Pat(Pat { sub_patterns, .. }) => sub_patterns
+                .iter()
+                .map(SubPattern::star_height)
+                .max()
+                .unwrap_or(0),
+            Self::Sub(sp) => sp.star_height(),
+        }
+    }
+    /// The longest chain of unbounded quantifiers applied through nested
+    /// groups; bounded quantifiers along the way don't count. This is the
+    /// pattern's star height under another name, for use as a hard limit
+    pub fn max_nested_unbounded_quantifiers(&self) -> usize {
+        self.star_height()
+    }
+    /// The number of capturing groups, named or not
+    pub fn capture_count(&self) -> usize {
+        let mut count = 0;
+        self.walk(&mut |sp| {
+            if sp.group().is_some_and(Group::is_capturing) {
+                count += 1;
+            }
+        });
+        count
+    }
+    /// The highest number assigned to a capturing group, or 0 if there are
+    /// none, for sizing a captures array. Branch-reset groups like `(?|...)`
+    /// don't parse, so groups are numbered in order and this is the same as
+    /// `capture_count`
+    pub fn max_group_number(&self) -> usize {
+        self.group_numbering().len()
+    }
+    /// A rough cost estimate for deciding whether to accept a pattern; see the
+    /// weight constants in this module for how it's put together
+    pub fn complexity_score(&self) -> u64 {
+        let cardinality: u64 = self.char_sets().iter().map(|cs| cs.cardinality()).sum();
+        let quantifier_max: u64 = self
+            .quantifiers()
+            .iter()
+            .map(|(q, _)| q.max_count().map_or(QUANTIFIER_MAX_CAP, |n| n as u64))
+            .sum();
+        self.depth() as u64 * DEPTH_WEIGHT
+            + self.star_height() as u64 * STAR_HEIGHT_WEIGHT
+            + self.capture_count() as u64 * CAPTURE_WEIGHT
+            + cardinality.min(CARDINALITY_CAP) / CARDINALITY_DIVISOR
+            + quantifier_max.min(QUANTIFIER_MAX_CAP)
+    }
+    /// Every character a match can start with. Backreferences could start with
+    /// anything, so they count as the entire range
+    pub fn first_set(&self) -> CharSet {
+        match self {
+            Self::Pat(Pat { sub_patterns, .. }) => SubPattern::sequence_first_set(sub_patterns).0,
+            Self::Sub(sp) => sp.first_set().0,
+        }
+    }
+    /// Which bytes a match can start with, as a prefilter table. `None` when a
+    /// match could be empty or start with a non-ascii character. Any
+    /// case-insensitivity adds the other case of every ascii letter
+    pub fn ascii_first_byte_set(&self) -> Option<[bool; 256]> {
+        let (set, nullable) = match self {
+            Self::Pat(Pat { sub_patterns, .. }) => SubPattern::sequence_first_set(sub_patterns),
+            Self::Sub(sp) => sp.first_set(),
+        };
+        if nullable
+            || set
+                .char_ranges()
+                .ranges_iter()
+                .any(|r| !r.as_bounds().1.is_ascii())
+        {
+            return None;
+        }
+        let mut ignore_case = self.flags().is_some_and(|f| f.contains(Flag::Ignorecase));
+        self.walk(&mut |sp| match sp {
+            SubPattern::SetFlags(flags) => ignore_case |= flags.contains(Flag::Ignorecase),
+            sp => {
+                if let Some(Group::Group { flags, .. }) = sp.group() {
+                    ignore_case |= flags.contains(Flag::Ignorecase);
+                }
+            }
+        });
+        let mut bytes = [false; 256];
+        for b in 0..0x80u8 {
+            let c = char::from(b);
+            bytes[usize::from(b)] = set.contains(c)
+                || ignore_case
+                    && (set.contains(c.to_ascii_lowercase())
+                        || set.contains(c.to_ascii_uppercase()));
+        }
+        Some(bytes)
+    }
+    /// Describe each alternation with two branches that can start with the
+    /// same character, making which one matches depend on their order
+    pub fn ambiguous_alternation(&self) -> Vec<String> {
+        let mut found = Vec::new();
+        self.walk_paths(&mut Vec::new(), &mut |sp, path| {
+            let SubPattern::Alternatives(Alternatives(branches)) = sp else {
+                return;
+            };
+            let firsts: Vec<CharSet> = branches.iter().map(|b| b.first_set().0).collect();
+            for (i, fst) in firsts.iter().enumerate() {
+                for (j, snd) in firsts.iter().enumerate().skip(i + 1) {
+                    if !fst.is_disjoint(snd) {
+                        found.push(format!(
+                            "{}: {} and {} can start the same way",
+                            path_string(path),
+                            branches[i].as_string(),
+                            branches[j].as_string()
+                        ));
+                    }
+                }
+            }
+        });
+        found
+    }
+    /// Unbounded quantifiers on something that can match nothing, like `(a?)*`,
+    /// which naive engines can loop on forever
+    pub fn empty_loop_risk(&self) -> Vec<String> {
+        let mut found = Vec::new();
+        self.walk_paths(&mut Vec::new(), &mut |sp, path| {
+            if let SubPattern::Quantified(Quantified {
+                quantifiable,
+                quantifier: Some(q),
+            }) = sp
+                && !q.is_finite()
+                && quantifiable.min_match_len() == 0
+            {
+                found.push(format!(
+                    "{}: {} repeats something that can match nothing",
+                    path_string(path),
+                    sp.as_string()
+                ));
+            }
+        });
+        found
+    }
+    /// Literals repeated by a `*`, `+` or `{n,}` quantifier, whether on the
+    /// literal itself or on an enclosing group, in document order. As written,
+    /// a quantifier after a run of literals only repeats the last character,
+    /// so `ab+c*` gives `b` and `c`, while `(ab)+` gives `ab`
+    pub fn unbounded_literals(&self) -> Vec<String> {
+        let mut found = Vec::new();
+        match self {
+            Self::Pat(Pat { sub_patterns, .. }) => {
+                for sp in sub_patterns {
+                    sp.unbounded_literals(false, &mut found);
+                }
+            }
+            Self::Sub(sp) => sp.unbounded_literals(false, &mut found),
+        }
+        found
+    }
+    /// Quantifiers on something that never consumes input, like `(?=a)*`.
+    /// Anchors can't be quantified, so these are always groups of lookaround
+    /// or other zero-width pieces
+    pub fn quantified_zero_width(&self) -> Vec<String> {
+        let mut found = Vec::new();
+        self.walk_paths(&mut Vec::new(), &mut |sp, path| {
+            if let SubPattern::Quantified(Quantified {
+                quantifiable: quantifiable @ Quantifiable::Group(_),
+                quantifier: Some(_),
+            }) = sp
+                && quantifiable.max_match_len() == Some(0)
+            {
+                found.push(format!(
+                    "{}: {} quantifies something zero-width",
+                    path_string(path),
+                    sp.as_string()
+                ));
+            }
+        });
+        found
+    }
+    /// Branches following one that matches anything, including nothing, and
+    /// so can never be chosen first
+    pub fn unreachable_branches(&self) -> Vec<String> {
+        let mut found = Vec::new();
+        self.walk_paths(&mut Vec::new(), &mut |sp, path| {
+            let SubPattern::Alternatives(Alternatives(branches)) = sp else {
+                return;
+            };
+            if let Some(ix) = branches.iter().position(SubPattern::always_matches) {
+                for branch in &branches[ix + 1..] {
+                    found.push(format!(
+                        "{}: {} is unreachable after {}",
+                        path_string(path),
+                        branch.as_string(),
+                        branches[ix].as_string()
+                    ));
+                }
+            }
+        });
+        found
+    }
+    /// The literal or character set covering character `char_ix`. Positions
+    /// index into `as_string_original`'s output, which follows the source for
+    /// character sets and flags; other spellings, like `\\x61` for `a`, are
+    /// measured as `as_string` writes them
+    pub fn element_at(&self, char_ix: usize) -> Option<ElementRef<'_>> {
+        match self {
+            Self::Pat(p) => {
+                let flags = p.flags.remove_flag(Flag::Verbose);
+                let start = if flags.is_empty() {
+                    0
+                } else {
+                    flags.as_string().chars().count() + 2
+                };
+                SubPattern::element_in_sequence(&p.sub_patterns, start, char_ix)
+            }
+            Self::Sub(sp) => sp.element_at(0, char_ix),
+        }
+    }
+    /// The flags in effect at `char_ix` of `as_string_original`: the whole-pattern
+    /// flags, updated by each enclosing scoped group and any inline flags
+    /// earlier in the same sequence
+    pub fn flags_at(&self, char_ix: usize) -> Flags {
+        match self {
+            Self::Pat(p) => {
+                let flags = p.flags.remove_flag(Flag::Verbose);
+                let start = if flags.is_empty() {
+                    0
+                } else {
+                    flags.as_string().chars().count() + 2
+                };
+                SubPattern::flags_in_sequence(
+                    &p.sub_patterns,
+                    start,
+                    char_ix,
+                    Flags::empty().apply(&p.flags),
+                )
+            }
+            Self::Sub(sp) => sp.flags_at(0, char_ix, Flags::empty()),
+        }
+    }
+    /// Every character set in the pattern in document order, including those
+    /// written as `\\d`, `\\w` or `\\s`
+    pub fn char_sets(&self) -> Vec<&CharSet> {
+        let mut sets = Vec::new();
+        self.walk(&mut |sp| {
+            if let SubPattern::Quantified(Quantified {
+                quantifiable: Quantifiable::Element(Element::CharSet(cs)),
+                ..
+            }) = sp
+            {
+                sets.push(cs);
+            }
+        });
+        sets
+    }
+    /// The text of every `(?#...)` comment, in document order
+    pub fn comments(&self) -> Vec<String> {
+        let mut comments = Vec::new();
+        self.walk(&mut |sp| {
+            if let SubPattern::Comment(text) = sp {
+                comments.push(text.clone());
+            }
+        });
+        comments
+    }
+    /// The character sets matching more than `threshold` characters, as
+    /// written, like the near-universal `[^a]`
+    pub fn broad_char_sets(&self, threshold: usize) -> Vec<String> {
+        self.char_sets()
+            .into_iter()
+            .filter(|cs| cs.cardinality() > threshold as u64)
+            .map(CharSet::as_string)
+            .collect()
+    }
+    /// Every quantifier in the pattern, paired with the `/`-separated path of
+    /// child indices leading to the element it applies to
+    pub fn quantifiers(&self) -> Vec<(&Quantifier, String)> {
+        let mut quantifiers = Vec::new();
+        self.walk_paths(&mut Vec::new(), &mut |sp, path| {
+            if let SubPattern::Quantified(Quantified {
+                quantifier: Some(q),
+                ..
+            }) = sp
+            {
+                quantifiers.push((q, path_string(path)));
+            }
+        });
+        quantifiers
+    }
+    /// Describe each exactly-repeated non-capturing group whose only content
+    /// is itself exactly repeated, e.g. `(?:a{2}){3}`, which could be `a{6}`
+    pub fn redundant_nested_quantifiers(&self) -> Vec<String> {
+        let mut found = Vec::new();
+        self.walk_paths(&mut Vec::new(), &mut |sp, path| {
+            let SubPattern::Quantified(Quantified {
+                quantifiable:
+                    Quantifiable::Group(Group::Group {
+                        ext: Some(GroupExt::NonCapturing),
+                        flags,
+                        components,
+                        ..
+                    }),
+                quantifier: Some(outer),
+            }) = sp
+            else {
+                return;
+            };
+            if let [
+                SubPattern::Quantified(Quantified {
+                    quantifiable,
+                    quantifier: Some(inner),
+                }),
+            ] = components.as_slice()
+                && flags.is_empty()
+                && let (Some(m), Some(n)) = (outer.exact_count(), inner.exact_count())
+            {
+                found.push(format!(
+                    "{}: {} could be {}{{{}}}",
+                    path_string(path),
+                    sp.as_string(),
+                    quantifiable.as_string(),
+                    m * n
+                ));
+            }
+        });
+        found
+    }
+    /// Downgrade every group with extension `ext`: atomic groups become
+    /// non-capturing, and non-capturing groups without flags become capturing.
+    /// Both change what the pattern means. Lookarounds can't be removed this way.
+    pub fn remove_group_ext(&self, ext: GroupExt) -> Result<Self> {
+        let replacement = match ext {
+            GroupExt::Atomic => Some(GroupExt::NonCapturing),
+            GroupExt::NonCapturing => None,
+            other => {
+                return Err(ReggieError::Unsupported {
+                    feature: format!("removing {} groups", other.as_string()),
+                }
+                .into());
+            }
+        };
+        Ok(self.transform(&mut |sp| match sp {
+            SubPattern::Quantified(Quantified {
+                quantifiable:
+                    Quantifiable::Group(Group::Group {
+                        ext: Some(e),
+                        flags,
+                        name,
+                        components,
+                    }),
+                quantifier,
+            }) if e == ext && (replacement.is_some() || flags.is_empty()) => {
+                SubPattern::Quantified(Quantified {
+                    quantifiable: Quantifiable::Group(Group::Group {
+                        ext: replacement.clone(),
+                        flags,
+                        name,
+                        components,
+                    }),
+                    quantifier,
+                })
+            }
+            other => other,
+        }))
+    }
+    /// Drop every `(?#...)` comment group. A comment that's the whole of an
+    /// alternation or conditional branch becomes an empty non-capturing group,
+    /// as does one whose removal would run a backreference into following
+    /// digits. Verbose mode doesn't change this: whitespace is already gone by
+    /// the time the pattern is parsed, so `(?x)a(?#c)b` comes out as `(?x)ab`.
+    pub fn remove_comments(&self) -> Self {
+        let stripped = self.transform(&mut |sp| match sp {
+            SubPattern::Quantified(Quantified {
+                quantifiable:
+                    Quantifiable::Group(Group::Group {
+                        ext,
+                        flags,
+                        name,
+                        components,
+                    }),
+                quantifier,
+            }) => SubPattern::Quantified(Quantified {
+                quantifiable: Quantifiable::Group(Group::Group {
+                    ext,
+                    flags,
+                    name,
+                    components: SubPattern::without_comments(components),
+                }),
+                quantifier,
+            }),
+            SubPattern::Quantified(Quantified {
+                quantifiable:
+                    Quantifiable::Group(Group::Ternary {
+                        group_id,
+                        yes_pat,
+                        no_pat,
+                    }),
+                quantifier,
+            }) => SubPattern::Quantified(Quantified {
+                quantifiable: Quantifiable::Group(Group::Ternary {
+                    group_id,
+                    yes_pat: Box::new(yes_pat.comment_to_empty_group()),
+                    no_pat: no_pat.map(|p| Box::new(p.comment_to_empty_group())),
+                }),
+                quantifier,
+            }),
+            SubPattern::Alternatives(alts) => {
+                SubPattern::Alternatives(Alternatives::from_components(
+                    alts.0
+                        .into_iter()
+                        .map(|sp| sp.comment_to_empty_group())
+                        .collect(),
+                ))
+            }
+            other => other,
+        });
+        match stripped {
+            Self::Pat(Pat {
+                flags,
+                sub_patterns,
+            }) => Self::Pat(Pat {
+                flags,
+                sub_patterns: SubPattern::without_comments(sub_patterns),
+            }),
+            Self::Sub(sp) => Self::Sub(sp.comment_to_empty_group()),
+        }
+    }
+    /// Whether `needle` appears, structurally, anywhere in the pattern: either
+    /// as a single sub-pattern or as a run of consecutive ones
+    pub fn contains_subpattern(&self, needle: &Self) -> bool {
+        let needle = match needle {
+            Self::Pat(Pat { sub_patterns, .. }) => sub_patterns.as_slice(),
+            Self::Sub(sp) => std::slice::from_ref(sp),
+        };
+        if needle.is_empty() {
+            return true;
+        }
+        let contains =
+            |haystack: &[SubPattern]| haystack.windows(needle.len()).any(|w| w == needle);
+        if let Self::Pat(Pat { sub_patterns, .. }) = self
+            && contains(sub_patterns)
+        {
+            return true;
+        }
+        let mut found = false;
+        self.walk(&mut |sp| {
+            found |= contains(std::slice::from_ref(sp))
+                || matches!(sp.group(), Some(Group::Group { components, .. }) if contains(components));
+        });
+        found
+    }
+    /// Rebuild the pattern bottom-up, passing every sub-pattern through `f`
+    pub(crate) fn transform(&self, f: &mut impl FnMut(SubPattern) -> SubPattern) -> Self {
+        self.try_transform(&mut |sp| Ok(f(sp)))
+            .expect("infallible transform")
+    }
+    pub(crate) fn try_transform(
+        &self,
+        f: &mut impl FnMut(SubPattern) -> Result<SubPattern>,
+    ) -> Result<Self> {
+        Ok(match self {
+            Self::Pat(Pat {
+                flags,
+                sub_patterns,
+            }) => Self::Pat(Pat {
+                flags: flags.clone(),
+                sub_patterns: sub_patterns
+                    .iter()
+                    .map(|sp| sp.try_transform(f))
+                    .collect::<Result<Vec<SubPattern>>>()?,
+            }),
+            Self::Sub(sp) => Self::Sub(sp.try_transform(f)?),
+        })
+    }
+    pub(crate) fn walk<'a>(&'a self, f: &mut impl FnMut(&'a SubPattern)) {
+        self.walk_paths(&mut Vec::new(), &mut |sp, _| f(sp));
+    }
+    pub(crate) fn walk_paths<'a>(
+        &'a self,
+        path: &mut Vec<usize>,
+        f: &mut impl FnMut(&'a SubPattern, &[usize]),
+    ) {
+        match self {
+            Self::Pat(Pat { sub_patterns, .. }) => {
+                for (ix, sp) in sub_patterns.iter().enumerate() {
+                    path.push(ix);
+                    sp.walk_paths(path, f);
+                    path.pop();
+                }
+            }
+            Self::Sub(sp) => sp.walk_paths(path, f),
+        }
+    }
+    /// Check that the stringified pattern parses back into an equivalent pattern
+    pub fn verify(&self) -> Result<()> {
+        let reparsed = Self::parse(&self.as_string())?;
+        if self.normalized() == reparsed.normalized() {
+            Ok(())
+        } else {
+            Err(ReggieError::RoundTripMismatch {
+                pattern: format!("{:?}", self),
+                reparsed: format!("{:?}", reparsed),
+            }
+            .into())
+        }
+    }
+    /// A canonical `Pat`, with adjacent unquantified literals merged. The
+    /// verbose flag is dropped since it has no effect once parsed
+    fn normalized(&self) -> Pat {
+        match self {
+            Self::Pat(Pat {
+                flags,
+                sub_patterns,
+            }) => Pat {
+                flags: flags.remove_flag(Flag::Verbose),
+                sub_patterns: SubPattern::normalize_sequence(sub_patterns),
+            },
+            Self::Sub(sp) => Pat {
+                flags: Flags::empty(),
+                sub_patterns: SubPattern::normalize_sequence(std::slice::from_ref(sp)),
+            },
+        }
+    }
+    #[allow(clippy::wrong_self_convention)]
+    fn into_subpattern(&self) -> SubPattern {
+        match self {
+            Self::Sub(sp) => sp.clone(),
+            Self::Pat(Pat {
+                flags,
+                sub_patterns,
+            }) if flags.is_empty() && sub_patterns.len() == 1 => sub_patterns[0].clone(),
+            Self::Pat(_) => {
+                let Self::Sub(s) = self.into_group() else {
+                    unreachable!()
+                };
+                s
+            }
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Hash)]
+pub struct Pat {
+    flags: Flags,
+    pub sub_patterns: Vec<SubPattern>,
+}
+
+impl Pat {
+    pub(crate) fn from_sub_patterns(sub_patterns: Vec<SubPattern>) -> Self {
+        Self {
+            flags: Flags::empty(),
+            sub_patterns,
+        }
+    }
+    pub fn from_pair(pair: Pair<Rule>) -> Result<Self> {
+        Self::from_pair_with_progress(pair, &mut |_, _| ())
+    }
+    pub(crate) fn from_pair_with_progress(
+        pair: Pair<Rule>,
+        on_progress: &mut impl FnMut(usize, usize),
+    ) -> Result<Self> {
+        let total = pair.as_span().end();
+        let mut flags = Flags::empty();
+        let mut sub_patterns = Vec::new();
+        for matched in pair.into_inner() {
+            match matched.as_rule() {
+                Rule::sub_pattern => {
+                    let consumed = matched.as_span().end();
+                    sub_patterns.push(SubPattern::from_pair(matched)?);
+                    on_progress(consumed, total);
+                }
+                Rule::whole_pattern_flags => {
+                    let mut parsed_flags = Flags::from_whole_pattern_pair(matched)?;
+                    std::mem::swap(&mut flags, &mut parsed_flags);
+                }
+                Rule::EOI => continue,
+                _ => return Err(ReggieError::unexpected_input(matched).into()),
+            }
+        }
+        Ok(Self {
+            flags,
+            sub_patterns,
+        })
+    }
+
+    pub(crate) fn from_json_value(json: &Json) -> Result<Self> {
+        Ok(Self {
+            flags: Flags::from_json_value(json.field("flags")?)?,
+            sub_patterns: SubPattern::sequence_from_json(json.field("sub_patterns")?)?,
+        })
+    }
+    pub(crate) fn to_json_value(&self) -> Json {
+        Json::tagged(
+            "Pat",
+            vec![
+                ("flags", self.flags.to_json_value()),
+                (
+                    "sub_patterns",
+                    Json::Array(
+                        self.sub_patterns
+                            .iter()
+                            .map(SubPattern::to_json_value)
+                            .collect(),
+                    ),
+                ),
+            ],
+        )
+    }
+    pub fn as_string(&self) -> String {
+        self.as_string_with(&EmitOptions::default())
+    }
+    pub fn as_string_with(&self, opts: &EmitOptions) -> String {
+        // verbose whitespace is stripped at parse time, so the flag is dropped
+        // rather than re-emitted
+        let flags = self.flags.remove_flag(Flag::Verbose);
+        let mut s = if flags.is_empty() {
+            String::new()
+        } else {
+            format!("({})", flags.as_string_with(opts))
+        };
+        for sp in self.sub_patterns.iter() {
+            write!(&mut s, "{}", sp.as_string_with(opts)).unwrap();
+        }
+        s
+    }
+    fn is_finite(&self) -> bool {
+        for sp in self.sub_patterns.iter() {
+            if !sp.is_finite() {
+                return false;
+            }
+        }
+        true
+    }
+    fn min_match_len(&self) -> usize {
+        self.sub_patterns.iter().map(|sp| sp.min_match_len()).sum()
+    }
+    fn without_flag(&self, flag: Flag) -> Pattern {
+        let mut new = self.clone();
+        let new_flags = new.flags.remove_flag(flag);
+        new.flags = new_flags;
+        Pattern::Pat(new)
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Hash)]
+pub enum SubPattern {
+    Alternatives(Alternatives),
+    Quantified(Quantified),
+    ZeroWidthLiteral(ZeroWidthLiteral),
+    Comment(String),
+    Backref(usize),
+    ControlVerb(VerbKind),
+    SetFlags(Flags),
+    /// Source text `parse_recover` couldn't parse, kept as written, with its
+    /// byte offsets. It matches nothing as far as analyses are concerned
+    Error {
+        raw: String,
+        span: (usize, usize),
+    },
+}
+
+impl SubPattern {
+    pub fn from_pair(pair: Pair<Rule>) -> Result<Self> {
+        let (_, char_ix) = pair.line_col();
+        let mut inner = pair.into_inner();
+        if let Some(p) = inner.next() {
+            SubPattern::single_from_pair(p, &mut inner)
+        } else {
+            Err(ReggieError::unexpected_eoi(char_ix).into())
+        }
+    }
+    pub fn single_from_pair(pair: Pair<Rule>, inner: &mut Pairs<'_, Rule>) -> Result<Self> {
+        match pair.as_rule() {
+            Rule::alternatives => SubPattern::alternatives_from_pair(pair),
+            Rule::group
+            | Rule::relative_backref
+            | Rule::literals
+            | Rule::char_set
+            | Rule::char_class
+            | Rule::code_point
+            | Rule::any_char => SubPattern::quantified_from_pair(pair, inner),
+            Rule::zero_width_literal => SubPattern::zwl_from_pair(pair),
+            Rule::comment_group => SubPattern::comment_group_from_pair(pair),
+            Rule::backref => SubPattern::backref_from_pair(pair),
+            Rule::control_verb => Ok(SubPattern::ControlVerb(VerbKind::from_pair(pair)?)),
+            Rule::set_flags => SubPattern::set_flags_from_pair(pair),
+            _ => {
+                println!("single_from_pair actually {:?}", pair.as_rule());
+                Err(ReggieError::unexpected_input(pair).into())
+            }
+        }
+    }
+    fn group_from_subpatterns(
+        components: Vec<Self>,
+        flags: Option<Flags>,
+        name: Option<String>,
+        ext: Option<GroupExt>,
+    ) -> Self {
+        Self::Quantified(Quantified::subpatterns_to_group(
+            components, flags, name, ext,
+        ))
+    }
+    fn new_alternatives(components: Vec<SubPattern>) -> Self {
+        Self::Alternatives(Alternatives::from_components(components))
+    }
+    fn new_char_set(ranges: Vec<(char, char)>, quantifier: Option<Quantifier>) -> Result<Self> {
+        Ok(Self::Quantified(Quantified::new_char_set_from_ranges(
+            ranges, quantifier,
+        )?))
+    }
+    fn new_char_class(cc: CClass, quantifier: Option<Quantifier>) -> Self {
+        Self::Quantified(Quantified::new_char_class(cc, quantifier))
+    }
+    fn new_literal(lit: String, quantifier: Option<Quantifier>) -> Self {
+        Self::Quantified(Quantified::new_literal(lit, quantifier))
+    }
+    pub fn as_group(&self) -> Self {
+        Self::Quantified(Quantified::subpatterns_to_group(
+            vec![self.clone()],
+            None,
+            None,
+            None,
+        ))
+    }
+    pub fn flags(&self) -> Flags {
         Flags::empty()
     }
-    pub fn groups_count(&self) -> usize {
+    pub fn groups_count(&self) -> usize {
+        match self {
+            Self::ZeroWidthLiteral(_)
+            | Self::Comment(_)
+            | Self::Backref(_)
+            | Self::ControlVerb(_)
+            | Self::SetFlags(_)
+            | Self::Error { .. } => 0,
+            Self::Alternatives(alts) => alts.groups_count(),
+            Self::Quantified(q) => q.groups_count(),
+        }
+    }
+    pub(crate) fn inner_components(inner: Pairs<'_, Rule>) -> Result<Vec<Self>> {
+        let mut comps: Vec<Self> = Vec::new();
+        for p in inner {
+            match p.as_rule() {
+                Rule::sub_pattern => comps.push(Self::from_pair(p)?),
+                Rule::r_parens => continue,
+                _ => {
+                    return Err(ReggieError::unexpected_input(p).into());
+                }
+            }
+        }
+        Ok(comps)
+    }
+
+    fn alternatives_from_pair(pair: Pair<Rule>) -> Result<Self> {
+        Ok(Self::Alternatives(Alternatives::from_pair(pair)?))
+    }
+    fn quantified_from_pair(pair: Pair<Rule>, inner: &mut Pairs<'_, Rule>) -> Result<Self> {
+        Ok(Self::Quantified(Quantified::from_pair(pair, inner)?))
+    }
+    fn zwl_from_pair(pair: Pair<Rule>) -> Result<Self> {
+        Ok(Self::ZeroWidthLiteral(ZeroWidthLiteral::from_pair(pair)?))
+    }
+    fn comment_group_from_pair(pair: Pair<Rule>) -> Result<Self> {
+        let (_, char_ix) = pair.line_col();
+        let mut inner = pair.into_inner();
+        let content = inner.nth(3).ok_or(ReggieError::unexpected_eoi(char_ix))?; // (?#
+        Ok(Self::Comment(content.as_str().into()))
+    }
+    fn set_flags_from_pair(pair: Pair<Rule>) -> Result<Self> {
+        let (_, char_ix) = pair.line_col();
+        let flags = pair
+            .into_inner()
+            .find(|p| p.as_rule() == Rule::pattern_flags)
+            .ok_or(ReggieError::unexpected_eoi(char_ix))?;
+        Ok(Self::SetFlags(Flags::from_pair(flags)?))
+    }
+    fn backref_from_pair(pair: Pair<Rule>) -> Result<Self> {
+        let n = pair
+            .as_str()
+            .strip_prefix('\\')
+            .and_then(|n| n.parse::<usize>().ok())
+            .ok_or_else(|| ReggieError::unexpected_input(pair))?;
+        Ok(Self::Backref(n))
+    }
+    pub(crate) fn from_json_value(json: &Json) -> Result<Self> {
+        match json.type_tag()? {
+            "Alternatives" => Ok(Self::Alternatives(Alternatives(Self::sequence_from_json(
+                json.field("branches")?,
+            )?))),
+            "Quantified" => Ok(Self::Quantified(Quantified::from_json_value(json)?)),
+            "ZeroWidthLiteral" => Ok(Self::ZeroWidthLiteral(ZeroWidthLiteral::from_json_value(
+                json,
+            )?)),
+            "Comment" => Ok(Self::Comment(json.field("text")?.as_str()?.into())),
+            "Backref" => Ok(Self::Backref(json.field("group")?.as_usize()?)),
+            "ControlVerb" => Ok(Self::ControlVerb(VerbKind::from_json_value(
+                json.field("verb")?,
+            )?)),
+            "SetFlags" => Ok(Self::SetFlags(Flags::from_json_value(
+                json.field("flags")?,
+            )?)),
+            "Error" => Ok(Self::Error {
+                raw: json.field("raw")?.as_str()?.into(),
+                span: (
+                    json.field("start")?.as_usize()?,
+                    json.field("end")?.as_usize()?,
+                ),
+            }),
+            other => Err(ReggieError::invalid_json(format!("unexpected type {}", other)).into()),
+        }
+    }
+    fn sequence_from_json(json: &Json) -> Result<Vec<Self>> {
+        json.as_array()?.iter().map(Self::from_json_value).collect()
+    }
+    pub(crate) fn to_json_value(&self) -> Json {
+        match self {
+            Self::Alternatives(Alternatives(branches)) => Json::tagged(
+                "Alternatives",
+                vec![(
+                    "branches",
+                    Json::Array(branches.iter().map(Self::to_json_value).collect()),
+                )],
+            ),
+            Self::Quantified(q) => q.to_json_value(),
+            Self::ZeroWidthLiteral(zwl) => zwl.to_json_value(),
+            Self::Comment(text) => Json::tagged("Comment", vec![("text", text.as_str().into())]),
+            Self::Backref(n) => Json::tagged("Backref", vec![("group", (*n).into())]),
+            Self::ControlVerb(verb) => {
+                Json::tagged("ControlVerb", vec![("verb", verb.to_json_value())])
+            }
+            Self::SetFlags(flags) => {
+                Json::tagged("SetFlags", vec![("flags", flags.to_json_value())])
+            }
+            Self::Error { raw, span } => Json::tagged(
+                "Error",
+                vec![
+                    ("raw", raw.as_str().into()),
+                    ("start", span.0.into()),
+                    ("end", span.1.into()),
+                ],
+            ),
+        }
+    }
+    pub fn as_string(&self) -> String {
+        self.as_string_with(&EmitOptions::default())
+    }
+    pub fn as_string_with(&self, opts: &EmitOptions) -> String {
+        match self {
+            Self::Alternatives(alts) => alts.as_string_with(opts),
+            Self::Quantified(quantified) => quantified.as_string_with(opts),
+            Self::ZeroWidthLiteral(zwl) => zwl.as_string_with(opts),
+            Self::Comment(c) => format!("(?#{})", c),
+            Self::Backref(n) => format!("\\{}", n),
+            Self::ControlVerb(v) => v.as_string(),
+            Self::SetFlags(flags) => format!("({})", flags.as_string_with(opts)),
+            Self::Error { raw, .. } => raw.clone(),
+        }
+    }
+    pub fn is_finite(&self) -> bool {
+        match self {
+            Self::Alternatives(alts) => alts.is_finite(),
+            Self::Quantified(quantified) => quantified.is_finite(),
+            _ => true,
+        }
+    }
+    pub fn min_match_len(&self) -> usize {
+        match self {
+            Self::Alternatives(alts) => alts.min_match_len(),
+            Self::Quantified(quantified) => quantified.min_match_len(),
+            Self::ZeroWidthLiteral(_) => 0,
+            Self::Comment(_) => 0,
+            //TODO(shr) depends on what the group matched
+            Self::Backref(_) => 0,
+            Self::ControlVerb(_) => 0,
+            Self::SetFlags(_) => 0,
+            Self::Error { .. } => 0,
+        }
+    }
+    pub(crate) fn tokens(&self, out: &mut Vec<Token>) {
+        match self {
+            Self::Alternatives(Alternatives(branches)) => {
+                for (ix, branch) in branches.iter().enumerate() {
+                    if ix > 0 {
+                        out.push(Token::Alternation(String::from("|")));
+                    }
+                    branch.tokens(out);
+                }
+            }
+            Self::Quantified(q) => q.tokens(out),
+            Self::Comment(_) => out.push(Token::Comment(self.as_string())),
+            Self::SetFlags(_) => out.push(Token::Flags(self.as_string())),
+            Self::Error { raw, .. } => out.push(Token::Error(raw.clone())),
+            Self::ZeroWidthLiteral(_) | Self::Backref(_) | Self::ControlVerb(_) => {
+                out.push(Token::Meta(self.as_string()))
+            }
+        }
+    }
+    pub fn max_match_len(&self) -> Option<usize> {
+        match self {
+            Self::Alternatives(alts) => alts.max_match_len(),
+            Self::Quantified(quantified) => quantified.max_match_len(),
+            Self::Backref(_) => None,
+            _ => Some(0),
+        }
+    }
+    pub(crate) fn possible_lengths(&self) -> Option<BTreeSet<usize>> {
+        match self {
+            Self::Alternatives(alts) => alts.possible_lengths(),
+            Self::Quantified(quantified) => quantified.possible_lengths(),
+            Self::Backref(_) => None,
+            _ => Some(BTreeSet::from([0])),
+        }
+    }
+    pub(crate) fn sequence_lengths(sps: &[Self]) -> Option<BTreeSet<usize>> {
+        sps.iter().try_fold(BTreeSet::from([0]), |acc, sp| {
+            Some(sum_lengths(&acc, &sp.possible_lengths()?))
+        })
+    }
+    pub fn fixed_width(&self) -> Option<usize> {
+        match self {
+            Self::Alternatives(alts) => alts.fixed_width(),
+            Self::Quantified(quantified) => quantified.fixed_width(),
+            Self::Backref(_) => None,
+            _ => Some(0),
+        }
+    }
+    fn without_flag(&self, flag: Flag) -> Pattern {
+        match self {
+            Self::Quantified(q) => Pattern::Sub(Self::Quantified(q.without_flag(flag))),
+            _ => Pattern::Sub(self.clone()),
+        }
+    }
+
+    fn as_pattern(&self) -> Pattern {
+        Pattern::Sub(self.clone())
+    }
+    pub(crate) fn normalized(&self) -> Self {
+        match self {
+            Self::Alternatives(alts) => Self::Alternatives(Alternatives::from_components(
+                alts.0.iter().map(Self::normalized).collect(),
+            )),
+            Self::Quantified(Quantified {
+                quantifiable: Quantifiable::Group(g),
+                quantifier,
+            }) => Self::Quantified(Quantified {
+                quantifiable: Quantifiable::Group(g.normalized()),
+                quantifier: *quantifier,
+            }),
+            other => other.clone(),
+        }
+    }
+    /// Turn the first `(?flags)` in a sequence, and everything after it, into
+    /// a `(?flags:...)` group, repeating for any later ones
+    fn scope_inline_flags(mut sps: Vec<Self>) -> Vec<Self> {
+        let Some(ix) = sps.iter().position(|sp| matches!(sp, Self::SetFlags(_))) else {
+            return sps;
+        };
+        let rest = sps.split_off(ix + 1);
+        let Some(Self::SetFlags(flags)) = sps.pop() else {
+            unreachable!()
+        };
+        if !rest.is_empty() {
+            sps.push(Self::group_from_subpatterns(
+                Self::scope_inline_flags(rest),
+                Some(flags),
+                None,
+                Some(GroupExt::NonCapturing),
+            ));
+        }
+        sps
+    }
+    pub(crate) fn normalize_sequence(sps: &[Self]) -> Vec<Self> {
+        let mut out: Vec<Self> = Vec::new();
+        for sp in sps.iter().map(Self::normalized) {
+            if let (Some(prev), Some(lit)) =
+                (out.last().and_then(Self::bare_literal), sp.bare_literal())
+            {
+                let merged = Self::new_literal(format!("{}{}", prev, lit), None);
+                out.pop();
+                out.push(merged);
+            } else {
+                out.push(sp);
+            }
+        }
+        out
+    }
+    /// Describe the sub-pattern, with `next` the number the next capturing
+    /// group gets
+    fn explain(&self, next: &mut usize) -> String {
+        match self {
+            Self::Alternatives(Alternatives(branches)) => {
+                let parts: Vec<String> = branches.iter().map(|b| b.explain(next)).collect();
+                match parts.split_last() {
+                    Some((last, rest)) if !rest.is_empty() => {
+                        format!("either {} or {}", rest.join(", "), last)
+                    }
+                    _ => parts.concat(),
+                }
+            }
+            Self::Quantified(Quantified {
+                quantifiable,
+                quantifier,
+            }) => {
+                let (singular, plural) = match quantifiable {
+                    Quantifiable::Element(e) => (e.explain(false), e.explain(true)),
+                    Quantifiable::Group(g) => {
+                        let singular = Self::explain_group(g, next);
+                        let plural = format!("repetitions of {}", singular);
+                        (singular, plural)
+                    }
+                };
+                match quantifier {
+                    Some(q) => q.explain(&singular, &plural),
+                    None => singular,
+                }
+            }
+            Self::ZeroWidthLiteral(zwl) => zwl.explain(),
+            Self::Comment(_) => String::new(),
+            Self::Backref(n) => format!("whatever group {} matched", n),
+            Self::ControlVerb(_) => format!("the control verb {}", self.as_string()),
+            Self::SetFlags(_) => format!("the flags {} from here on", self.as_string()),
+            Self::Error { raw, .. } => format!("unparsed text '{}'", raw),
+        }
+    }
+    fn explain_sequence(sps: &[Self], next: &mut usize) -> String {
+        let parts: Vec<String> = sps
+            .iter()
+            .filter(|sp| !matches!(sp, Self::Comment(_)))
+            .map(|sp| sp.explain(next))
+            .collect();
+        if parts.is_empty() {
+            String::from("nothing")
+        } else {
+            parts.join(", then ")
+        }
+    }
+    fn explain_group(g: &Group, next: &mut usize) -> String {
+        match g {
+            Group::NamedBackref { name } => format!("whatever group '{}' matched", name),
+            Group::RelativeBackref { offset } => {
+                format!("whatever the group {:+} from here matched", offset)
+            }
+            Group::Ternary {
+                group_id,
+                yes_pat,
+                no_pat,
+            } => {
+                let yes = yes_pat.explain(next);
+                match no_pat {
+                    Some(no) => format!(
+                        "if group {} matched, {}, otherwise {}",
+                        group_id.as_string(),
+                        yes,
+                        no.explain(next)
+                    ),
+                    None => format!("if group {} matched, {}", group_id.as_string(), yes),
+                }
+            }
+            Group::Group {
+                ext,
+                flags,
+                name,
+                components,
+            } => {
+                let number = g.is_capturing().then(|| {
+                    *next += 1;
+                    *next - 1
+                });
+                let inner = format!(
+                    "{}{}",
+                    Self::explain_sequence(components, next),
+                    flags.explain()
+                );
+                match (ext, number, name) {
+                    (_, Some(n), Some(name)) => {
+                        format!("{} (captured as group {}, named '{}')", inner, n, name)
+                    }
+                    (_, Some(n), None) => format!("{} (captured as group {})", inner, n),
+                    (Some(GroupExt::Atomic), ..) => format!("{} (without backtracking)", inner),
+                    (Some(GroupExt::PosLookahead), ..) => format!("followed by {}", inner),
+                    (Some(GroupExt::NegLookahead), ..) => format!("not followed by {}", inner),
+                    (Some(GroupExt::PosLookbehind), ..) => format!("preceded by {}", inner),
+                    (Some(GroupExt::NegLookbehind), ..) => format!("not preceded by {}", inner),
+                    _ => inner,
+                }
+            }
+        }
+    }
+    /// Merge each run of equal unquantified elements into one with an
+    /// exact count
+    fn collapse_runs(sps: Vec<Self>) -> Vec<Self> {
+        let mut out: Vec<Self> = Vec::new();
+        let mut run = 0;
+        for sp in sps {
+            let repeats = matches!(
+                &sp,
+                Self::Quantified(Quantified {
+                    quantifiable: Quantifiable::Element(_),
+                    quantifier: None,
+                })
+            ) && out.last() == Some(&sp);
+            if repeats {
+                run += 1;
+                continue;
+            }
+            Self::finish_run(&mut out, run);
+            run = 1;
+            out.push(sp);
+        }
+        Self::finish_run(&mut out, run);
+        out
+    }
+    fn finish_run(out: &mut [Self], run: usize) {
+        if run > 1
+            && let Some(Self::Quantified(q)) = out.last_mut()
+        {
+            q.quantifier = Some(Quantifier::exactly(run));
+        }
+    }
+    /// The characters a match can start with, and whether it can be empty
+    pub(crate) fn first_set(&self) -> (CharSet, bool) {
+        match self {
+            Self::Alternatives(Alternatives(branches)) => {
+                branches.iter().map(Self::first_set).fold(
+                    (CharSet::empty(), false),
+                    |(set, nullable), (branch_set, branch_nullable)| {
+                        (set.union(&branch_set), nullable || branch_nullable)
+                    },
+                )
+            }
+            Self::Quantified(Quantified {
+                quantifiable,
+                quantifier,
+            }) => {
+                let (set, nullable) = match quantifiable {
+                    Quantifiable::Element(Element::CharSet(cs)) => (cs.clone(), false),
+                    Quantifiable::Element(Element::AnyChar) => (CharSet::entire(), false),
+                    Quantifiable::Element(Element::Literal(l)) => match l.first_char() {
+                        Some(c) => (CharSet::from_char(c), false),
+                        None => (CharSet::empty(), true),
+                    },
+                    Quantifiable::Group(
+                        Group::NamedBackref { .. } | Group::RelativeBackref { .. },
+                    ) => (CharSet::entire(), true),
+                    Quantifiable::Group(Group::Ternary {
+                        yes_pat, no_pat, ..
+                    }) => {
+                        let (yes_set, yes_nullable) = yes_pat.first_set();
+                        let (no_set, no_nullable) = no_pat
+                            .as_ref()
+                            .map_or((CharSet::empty(), true), |p| p.first_set());
+                        (yes_set.union(&no_set), yes_nullable || no_nullable)
+                    }
+                    Quantifiable::Group(Group::Group {
+                        ext:
+                            Some(
+                                GroupExt::PosLookahead
+                                | GroupExt::NegLookahead
+                                | GroupExt::PosLookbehind
+                                | GroupExt::NegLookbehind,
+                            ),
+                        ..
+                    }) => (CharSet::empty(), true),
+                    Quantifiable::Group(Group::Group { components, .. }) => {
+                        Self::sequence_first_set(components)
+                    }
+                };
+                let optional = quantifier.is_some_and(|q| q.min_len_multiplier() == 0);
+                (set, nullable || optional)
+            }
+            Self::Backref(_) => (CharSet::entire(), true),
+            _ => (CharSet::empty(), true),
+        }
+    }
+    /// Only optional single elements that can start with any character, like
+    /// `.*`, count; groups and backrefs are never assumed to always match
+    fn always_matches(&self) -> bool {
+        matches!(
+            self,
+            Self::Quantified(Quantified {
+                quantifiable: Quantifiable::Element(_),
+                ..
+            })
+        ) && self.first_set() == (CharSet::entire(), true)
+    }
+    pub(crate) fn sequence_first_set(sps: &[Self]) -> (CharSet, bool) {
+        let mut set = CharSet::empty();
+        for sp in sps {
+            let (sp_set, nullable) = sp.first_set();
+            set = set.union(&sp_set);
+            if !nullable {
+                return (set, false);
+            }
+        }
+        (set, true)
+    }
+    /// The flags in effect at `char_ix`, given the `active` flags where this
+    /// sub-pattern's output starts at `start`
+    fn flags_at(&self, start: usize, char_ix: usize, active: Flags) -> Flags {
+        match self {
+            Self::Alternatives(Alternatives(branches)) => {
+                let mut offset = start;
+                for branch in branches {
+                    let width = branch.as_string_with(&original_opts()).chars().count();
+                    if (offset..offset + width).contains(&char_ix) {
+                        return branch.flags_at(offset, char_ix, active);
+                    }
+                    offset += width + 1;
+                }
+                active
+            }
+            Self::Quantified(Quantified {
+                quantifiable:
+                    Quantifiable::Group(
+                        g @ Group::Group {
+                            flags, components, ..
+                        },
+                    ),
+                ..
+            }) => {
+                let inner: usize = components
+                    .iter()
+                    .map(|c| c.as_string_with(&original_opts()).chars().count())
+                    .sum();
+                let prefix = g.as_string_with(&original_opts()).chars().count() - inner - 1;
+                Self::flags_in_sequence(components, start + prefix, char_ix, active.apply(flags))
+            }
+            Self::Quantified(Quantified {
+                quantifiable:
+                    Quantifiable::Group(Group::Ternary {
+                        group_id,
+                        yes_pat,
+                        no_pat,
+                    }),
+                ..
+            }) => {
+                let yes_start = start + group_id.as_string().chars().count() + 4;
+                let no_start =
+                    yes_start + yes_pat.as_string_with(&original_opts()).chars().count() + 1;
+                match no_pat {
+                    Some(no) if char_ix >= no_start => no.flags_at(no_start, char_ix, active),
+                    _ => yes_pat.flags_at(yes_start, char_ix, active),
+                }
+            }
+            _ => active,
+        }
+    }
+    fn flags_in_sequence(sps: &[Self], start: usize, char_ix: usize, mut active: Flags) -> Flags {
+        let mut offset = start;
+        for sp in sps {
+            if let Self::SetFlags(flags) = sp {
+                active = active.apply(flags);
+            }
+            let width = sp.as_string_with(&original_opts()).chars().count();
+            if (offset..offset + width).contains(&char_ix) {
+                return sp.flags_at(offset, char_ix, active);
+            }
+            offset += width;
+        }
+        active
+    }
+    /// The element covering `char_ix`, with this sub-pattern's output
+    /// starting at `start`
+    fn element_at(&self, start: usize, char_ix: usize) -> Option<ElementRef<'_>> {
+        let width = self.as_string_with(&original_opts()).chars().count();
+        if !(start..start + width).contains(&char_ix) {
+            return None;
+        }
         match self {
-            Self::ZeroWidthLiteral(_) | Self::Comment(_) => 0,
-            Self::Alternatives(alts) => alts.groups_count(),
-            Self::Quantified(q) => q.groups_count(),
+            Self::Alternatives(Alternatives(branches)) => {
+                let mut offset = start;
+                for branch in branches {
+                    if let Some(found) = branch.element_at(offset, char_ix) {
+                        return Some(found);
+                    }
+                    offset += branch.as_string_with(&original_opts()).chars().count() + 1;
+                }
+                None
+            }
+            Self::Quantified(Quantified { quantifiable, .. }) => match quantifiable {
+                Quantifiable::Element(e) => {
+                    let end = start + e.as_string_with(&original_opts()).chars().count();
+                    match e {
+                        Element::Literal(l) if char_ix < end => Some(ElementRef::Literal(l)),
+                        Element::CharSet(cs) if char_ix < end => Some(ElementRef::CharSet(cs)),
+                        _ => None,
+                    }
+                }
+                Quantifiable::Group(g @ Group::Group { components, .. }) => {
+                    let inner: usize = components
+                        .iter()
+                        .map(|c| c.as_string_with(&original_opts()).chars().count())
+                        .sum();
+                    // everything but the closing parenthesis follows the components
+                    let prefix = g.as_string_with(&original_opts()).chars().count() - inner - 1;
+                    Self::element_in_sequence(components, start + prefix, char_ix)
+                }
+                Quantifiable::Group(Group::Ternary {
+                    group_id,
+                    yes_pat,
+                    no_pat,
+                }) => {
+                    let yes_start = start + group_id.as_string().chars().count() + 4;
+                    yes_pat.element_at(yes_start, char_ix).or_else(|| {
+                        let no_start = yes_start
+                            + yes_pat.as_string_with(&original_opts()).chars().count()
+                            + 1;
+                        no_pat.as_ref()?.element_at(no_start, char_ix)
+                    })
+                }
+                Quantifiable::Group(Group::NamedBackref { .. } | Group::RelativeBackref { .. }) => {
+                    None
+                }
+            },
+            _ => None,
         }
     }
-    pub(crate) fn inner_components(inner: Pairs<'_, Rule>) -> Result<Vec<Self>> {
-        let mut comps: Vec<Self> = Vec::new();
-        for p in inner {
-            match p.as_rule() {
-                Rule::sub_pattern => comps.push(Self::from_pair(p)?),
-                Rule::r_parens => continue,
-                _ => {
-                    return Err(ReggieError::unexpected_input(p).into());
+    fn element_in_sequence(sps: &[Self], start: usize, char_ix: usize) -> Option<ElementRef<'_>> {
+        let mut offset = start;
+        for sp in sps {
+            if let Some(found) = sp.element_at(offset, char_ix) {
+                return Some(found);
+            }
+            offset += sp.as_string_with(&original_opts()).chars().count();
+        }
+        None
+    }
+    pub(crate) fn star_height(&self) -> usize {
+        match self {
+            Self::Alternatives(Alternatives(branches)) => {
+                branches.iter().map(Self::star_height).max().unwrap_or(0)
+            }
+            Self::Quantified(Quantified {
+                quantifiable,
+                quantifier,
+            }) => {
+                let inner = match quantifiable {
+                    Quantifiable::Element(_) => 0,
+                    Quantifiable::Group(g) => g
+                        .children()
+                        .into_iter()
+                        .map(Self::star_height)
+                        .max()
+                        .unwrap_or(0),
+                };
+                inner + usize::from(quantifier.is_some_and(|q| !q.is_finite()))
+            }
+            _ => 0,
+        }
+    }
+    /// Collect the literals `unbounded_literals` reports, `enclosed` saying
+    /// whether an enclosing quantifier is already unbounded
+    fn unbounded_literals(&self, enclosed: bool, found: &mut Vec<String>) {
+        match self {
+            Self::Alternatives(Alternatives(branches)) => {
+                for branch in branches {
+                    branch.unbounded_literals(enclosed, found);
+                }
+            }
+            Self::Quantified(Quantified {
+                quantifiable,
+                quantifier,
+            }) => {
+                let unbounded = quantifier.is_some_and(|q| !q.is_finite());
+                match quantifiable {
+                    Quantifiable::Element(Element::Literal(l)) if enclosed => {
+                        found.push(String::from(l.text()))
+                    }
+                    Quantifiable::Element(Element::Literal(l)) if unbounded => {
+                        found.extend(l.text().chars().last().map(String::from))
+                    }
+                    Quantifiable::Element(_) => (),
+                    Quantifiable::Group(g) => {
+                        for child in g.children() {
+                            child.unbounded_literals(enclosed || unbounded, found);
+                        }
+                    }
                 }
             }
+            _ => (),
         }
-        Ok(comps)
     }
-
-    fn alternatives_from_pair(pair: Pair<Rule>) -> Result<Self> {
-        Ok(Self::Alternatives(Alternatives::from_pair(pair)?))
+    pub(crate) fn without_comments(sps: Vec<Self>) -> Vec<Self> {
+        let mut out: Vec<Self> = Vec::new();
+        let mut sps = sps.into_iter().peekable();
+        while let Some(sp) = sps.next() {
+            if !matches!(sp, Self::Comment(_)) {
+                out.push(sp);
+                continue;
+            }
+            let joins_backref = matches!(out.last(), Some(Self::Backref(_)))
+                && sps
+                    .peek()
+                    .is_some_and(|next| next.as_string().starts_with(|c: char| c.is_ascii_digit()));
+            if joins_backref {
+                out.push(sp.comment_to_empty_group());
+            }
+        }
+        out
     }
-    fn quantified_from_pair(pair: Pair<Rule>, inner: &mut Pairs<'_, Rule>) -> Result<Self> {
-        Ok(Self::Quantified(Quantified::from_pair(pair, inner)?))
+    /// Rebuild with every capturing group passed through `f` along with its
+    /// number, `next` being the number the next one in document order gets
+    fn map_captures(
+        &self,
+        next: &mut usize,
+        f: &mut impl FnMut(usize, Quantified) -> Quantified,
+    ) -> Self {
+        match self {
+            Self::Alternatives(alts) => Self::Alternatives(Alternatives::from_components(
+                alts.0.iter().map(|sp| sp.map_captures(next, f)).collect(),
+            )),
+            Self::Quantified(Quantified {
+                quantifiable: Quantifiable::Group(g),
+                quantifier,
+            }) => {
+                let number = g.is_capturing().then(|| {
+                    *next += 1;
+                    *next - 1
+                });
+                let group = match g {
+                    Group::Group {
+                        ext,
+                        flags,
+                        name,
+                        components,
+                    } => Group::Group {
+                        ext: ext.clone(),
+                        flags: flags.clone(),
+                        name: name.clone(),
+                        components: components
+                            .iter()
+                            .map(|sp| sp.map_captures(next, f))
+                            .collect(),
+                    },
+                    Group::Ternary {
+                        group_id,
+                        yes_pat,
+                        no_pat,
+                    } => Group::Ternary {
+                        group_id: group_id.clone(),
+                        yes_pat: Box::new(yes_pat.map_captures(next, f)),
+                        no_pat: no_pat.as_ref().map(|p| Box::new(p.map_captures(next, f))),
+                    },
+                    other => other.clone(),
+                };
+                let q = Quantified {
+                    quantifiable: Quantifiable::Group(group),
+                    quantifier: *quantifier,
+                };
+                Self::Quantified(match number {
+                    Some(n) => f(n, q),
+                    None => q,
+                })
+            }
+            other => other.clone(),
+        }
     }
-    fn zwl_from_pair(pair: Pair<Rule>) -> Result<Self> {
-        Ok(Self::ZeroWidthLiteral(ZeroWidthLiteral::from_pair(pair)?))
+    fn comment_to_empty_group(self) -> Self {
+        if let Self::Comment(_) = self {
+            Self::group_from_subpatterns(Vec::new(), None, None, Some(GroupExt::NonCapturing))
+        } else {
+            self
+        }
     }
-    fn comment_group_from_pair(pair: Pair<Rule>) -> Result<Self> {
-        let (_, char_ix) = pair.line_col();
-        let inner = pair.into_inner();
-        let content = inner
-            .skip(3)
-            .next()
-            .ok_or(ReggieError::unexpected_eoi(char_ix))?; // (?#
-        Ok(Self::Comment(content.as_str().into()))
+    /// The sequences this could be replaced by an alternation of, or `None`
+    /// if there are more than `MAX_EXPANDED_BRANCHES`
+    fn branches(&self) -> Option<Vec<Vec<Self>>> {
+        match self {
+            Self::Alternatives(Alternatives(alts)) => {
+                let mut out = Vec::new();
+                for alt in alts.iter() {
+                    out.extend(alt.branches()?);
+                    if out.len() > MAX_EXPANDED_BRANCHES {
+                        return None;
+                    }
+                }
+                Some(out)
+            }
+            Self::Quantified(Quantified {
+                quantifiable:
+                    Quantifiable::Group(Group::Group {
+                        ext: None | Some(GroupExt::NonCapturing),
+                        flags,
+                        components,
+                        ..
+                    }),
+                quantifier: None,
+            }) if flags.is_empty() => Self::sequence_branches(components),
+            other => Some(vec![vec![other.clone()]]),
+        }
     }
-    pub fn as_string(&self) -> String {
+    fn sequence_branches(sps: &[Self]) -> Option<Vec<Vec<Self>>> {
+        sps.iter().try_fold(vec![Vec::new()], |acc, sp| {
+            let options = sp.branches()?;
+            if acc.len() * options.len() > MAX_EXPANDED_BRANCHES {
+                return None;
+            }
+            Some(
+                acc.iter()
+                    .flat_map(|prefix| {
+                        options.iter().map(move |option| {
+                            let mut seq = prefix.clone();
+                            seq.extend(option.iter().cloned());
+                            seq
+                        })
+                    })
+                    .collect(),
+            )
+        })
+    }
+    /// A branch as a sequence to find shared ends in: the contents of a plain
+    /// `(?:...)` group, with unquantified literals split into single characters
+    fn factoring_units(&self) -> Vec<Self> {
+        let seq = match self {
+            Self::Quantified(Quantified {
+                quantifiable:
+                    Quantifiable::Group(Group::Group {
+                        ext: Some(GroupExt::NonCapturing),
+                        flags,
+                        components,
+                        ..
+                    }),
+                quantifier: None,
+            }) if flags.is_empty() => components.clone(),
+            other => vec![other.clone()],
+        };
+        seq.into_iter()
+            .flat_map(|sp| match sp.bare_literal() {
+                Some(text) => text
+                    .chars()
+                    .map(|c| Self::new_literal(c.to_string(), None))
+                    .collect(),
+                None => vec![sp],
+            })
+            .collect()
+    }
+    fn enumerate(&self, max_len: usize) -> Result<BTreeSet<String>> {
+        let unsupported = || -> anyhow::Error {
+            ReggieError::Unsupported {
+                feature: format!("enumerating {}", self.as_string()),
+            }
+            .into()
+        };
         match self {
-            Self::Alternatives(alts) => alts.as_string(),
-            Self::Quantified(quantified) => quantified.as_string(),
-            Self::ZeroWidthLiteral(zwl) => zwl.as_string(),
-            Self::Comment(c) => format!("(?#{})", c),
+            Self::Alternatives(Alternatives(branches)) => {
+                let mut out = BTreeSet::new();
+                for branch in branches {
+                    out.extend(branch.enumerate(max_len)?);
+                    if out.len() > MAX_ENUMERATED_STRINGS {
+                        return Err(unsupported());
+                    }
+                }
+                Ok(out)
+            }
+            Self::Quantified(Quantified {
+                quantifiable,
+                quantifier,
+            }) => {
+                let base = match quantifiable {
+                    Quantifiable::Element(Element::Literal(l)) => {
+                        let mut base = BTreeSet::new();
+                        if l.min_match_len() <= max_len {
+                            base.insert(l.text().to_string());
+                        }
+                        base
+                    }
+                    Quantifiable::Element(Element::CharSet(cs)) => {
+                        if cs.cardinality() > MAX_ENUMERATED_CHARS {
+                            return Err(unsupported());
+                        }
+                        if max_len == 0 {
+                            BTreeSet::new()
+                        } else {
+                            cs.char_ranges()
+                                .ranges_iter()
+                                .flat_map(|r| {
+                                    let (low, high) = r.as_bounds();
+                                    (low..=high).map(String::from)
+                                })
+                                .collect()
+                        }
+                    }
+                    Quantifiable::Group(Group::Group {
+                        ext: None | Some(GroupExt::NonCapturing) | Some(GroupExt::Atomic),
+                        flags,
+                        components,
+                        ..
+                    }) if !flags.contains(Flag::Ignorecase) => {
+                        Self::enumerate_sequence(components, max_len)?
+                    }
+                    _ => return Err(unsupported()),
+                };
+                let Some(q) = quantifier else {
+                    return Ok(base);
+                };
+                let mut out = BTreeSet::new();
+                if q.min_len_multiplier() == 0 {
+                    out.insert(String::new());
+                }
+                let mut current = BTreeSet::from([String::new()]);
+                for n in 1.. {
+                    if q.max_count().is_some_and(|max| n > max) {
+                        break;
+                    }
+                    let next = concat_strings(&current, &base, max_len).ok_or_else(unsupported)?;
+                    if next.is_empty() || next == current {
+                        break;
+                    }
+                    if n >= q.min_len_multiplier() {
+                        out.extend(next.iter().cloned());
+                    }
+                    current = next;
+                }
+                Ok(out)
+            }
+            Self::Comment(_) => Ok(BTreeSet::from([String::new()])),
+            Self::SetFlags(flags) if !flags.contains(Flag::Ignorecase) => {
+                Ok(BTreeSet::from([String::new()]))
+            }
+            _ => Err(unsupported()),
         }
     }
-    pub(crate) fn nth_group(&self, n: usize) -> Option<Pattern> {
-        if n == 0 {
-            Some(Pattern::Sub(self.clone()))
-        } else {
-            match self {
-                Self::Alternatives(alts) => alts.nth_group(n),
-                Self::Quantified(q) => q.nth_group(n),
-                _ => None,
+    fn enumerate_sequence(sps: &[Self], max_len: usize) -> Result<BTreeSet<String>> {
+        let mut out = BTreeSet::from([String::new()]);
+        for sp in sps {
+            out = concat_strings(&out, &sp.enumerate(max_len)?, max_len).ok_or_else(|| {
+                ReggieError::Unsupported {
+                    feature: format!("enumerating more than {} strings", MAX_ENUMERATED_STRINGS),
+                }
+            })?;
+        }
+        Ok(out)
+    }
+    fn matches_everything(&self, active: &Flags) -> bool {
+        match self {
+            Self::Alternatives(Alternatives(branches)) => {
+                branches.iter().any(|b| b.matches_everything(active))
+            }
+            Self::Quantified(Quantified {
+                quantifiable: Quantifiable::Element(e),
+                quantifier: Some(q),
+            }) => {
+                let any_char = match e {
+                    Element::AnyChar => active.contains(Flag::Dotall),
+                    Element::CharSet(cs) => *cs == CharSet::entire(),
+                    Element::Literal(_) => false,
+                };
+                any_char && q.min_len_multiplier() == 0 && !q.is_finite()
+            }
+            Self::Quantified(Quantified {
+                quantifiable:
+                    Quantifiable::Group(Group::Group {
+                        ext: None | Some(GroupExt::NonCapturing) | Some(GroupExt::Atomic),
+                        flags,
+                        components,
+                        ..
+                    }),
+                quantifier,
+            }) => {
+                quantifier.is_none_or(|q| q.min_len_multiplier() <= 1)
+                    && Self::sequence_matches_everything(components, &active.apply(flags))
             }
+            _ => false,
         }
     }
-    pub fn is_finite(&self) -> bool {
+    fn sequence_matches_everything(sps: &[Self], active: &Flags) -> bool {
+        let mut rest = sps.iter().filter(|sp| !matches!(sp, Self::Comment(_)));
+        rest.next().is_some_and(|sp| sp.matches_everything(active)) && rest.next().is_none()
+    }
+    fn must_match_at_start(&self, multiline: bool) -> bool {
         match self {
-            Self::Alternatives(alts) => alts.is_finite(),
-            Self::Quantified(quantified) => quantified.is_finite(),
-            _ => true,
+            Self::ZeroWidthLiteral(ZeroWidthLiteral::InputStart) => true,
+            Self::ZeroWidthLiteral(ZeroWidthLiteral::LineStart) => !multiline,
+            Self::Alternatives(Alternatives(branches)) => branches
+                .iter()
+                .all(|branch| branch.must_match_at_start(multiline)),
+            Self::Quantified(Quantified {
+                quantifiable:
+                    Quantifiable::Group(Group::Group {
+                        ext: None | Some(GroupExt::NonCapturing) | Some(GroupExt::Atomic),
+                        flags,
+                        components,
+                        ..
+                    }),
+                quantifier,
+            }) if quantifier.is_none_or(|q| q.min_len_multiplier() > 0) => {
+                let outer = if multiline {
+                    Flags::new_single(Flag::Multiline)
+                } else {
+                    Flags::empty()
+                };
+                Self::sequence_must_match_at_start(
+                    components,
+                    outer.apply(flags).contains(Flag::Multiline),
+                )
+            }
+            _ => false,
         }
     }
-    pub fn min_match_len(&self) -> usize {
+    fn sequence_must_match_at_start(sps: &[Self], mut multiline: bool) -> bool {
+        for sp in sps {
+            match sp {
+                Self::Comment(_) => continue,
+                Self::SetFlags(flags) => multiline |= flags.contains(Flag::Multiline),
+                sp => return sp.must_match_at_start(multiline),
+            }
+        }
+        false
+    }
+    fn is_end_anchored(&self) -> bool {
         match self {
-            Self::Alternatives(alts) => alts.min_match_len(),
-            Self::Quantified(quantified) => quantified.min_match_len(),
-            Self::ZeroWidthLiteral(_) => 0,
-            Self::Comment(_) => 0,
+            Self::ZeroWidthLiteral(
+                ZeroWidthLiteral::InputEnd
+                | ZeroWidthLiteral::InputEndBeforeNewline
+                | ZeroWidthLiteral::LineEnd,
+            ) => true,
+            Self::Alternatives(Alternatives(branches)) => {
+                branches.iter().all(Self::is_end_anchored)
+            }
+            Self::Quantified(Quantified {
+                quantifiable:
+                    Quantifiable::Group(Group::Group {
+                        ext: None | Some(GroupExt::NonCapturing) | Some(GroupExt::Atomic),
+                        components,
+                        ..
+                    }),
+                quantifier: None,
+            }) => Self::sequence_is_end_anchored(components),
+            _ => false,
         }
     }
-    fn without_flag(&self, flag: Flag) -> Pattern {
+    fn sequence_is_end_anchored(sps: &[Self]) -> bool {
+        sps.iter()
+            .rfind(|sp| !matches!(sp, Self::Comment(_)))
+            .is_some_and(Self::is_end_anchored)
+    }
+    fn required_literals(&self, out: &mut Vec<String>) {
+        let Self::Quantified(Quantified {
+            quantifiable,
+            quantifier,
+        }) = self
+        else {
+            return;
+        };
+        if quantifier.is_some_and(|q| q.min_len_multiplier() == 0) {
+            return;
+        }
+        match quantifiable {
+            Quantifiable::Element(Element::Literal(l)) => out.push(l.text().to_string()),
+            Quantifiable::Group(Group::Group {
+                ext: None | Some(GroupExt::NonCapturing) | Some(GroupExt::Atomic),
+                components,
+                ..
+            }) => {
+                for sp in components.iter() {
+                    sp.required_literals(out);
+                }
+            }
+            _ => (),
+        }
+    }
+    fn bare_literal(&self) -> Option<String> {
+        if let Self::Quantified(Quantified {
+            quantifiable: Quantifiable::Element(Element::Literal(l)),
+            quantifier: None,
+        }) = self
+        {
+            Some(l.text().to_string())
+        } else {
+            None
+        }
+    }
+    pub(crate) fn group(&self) -> Option<&Group> {
+        if let Self::Quantified(Quantified {
+            quantifiable: Quantifiable::Group(g),
+            ..
+        }) = self
+        {
+            Some(g)
+        } else {
+            None
+        }
+    }
+    pub(crate) fn try_transform(
+        &self,
+        f: &mut impl FnMut(SubPattern) -> Result<SubPattern>,
+    ) -> Result<Self> {
+        let rebuilt = match self {
+            Self::Alternatives(alts) => Self::Alternatives(Alternatives::from_components(
+                alts.0
+                    .iter()
+                    .map(|sp| sp.try_transform(f))
+                    .collect::<Result<Vec<Self>>>()?,
+            )),
+            Self::Quantified(Quantified {
+                quantifiable: Quantifiable::Group(g),
+                quantifier,
+            }) => Self::Quantified(Quantified {
+                quantifiable: Quantifiable::Group(g.try_transform(f)?),
+                quantifier: *quantifier,
+            }),
+            other => other.clone(),
+        };
+        f(rebuilt)
+    }
+    /// Visit this sub-pattern and everything beneath it, in document order,
+    /// along with the path of child indices leading to each
+    pub(crate) fn walk_paths<'a>(
+        &'a self,
+        path: &mut Vec<usize>,
+        f: &mut impl FnMut(&'a SubPattern, &[usize]),
+    ) {
+        f(self, path);
         match self {
-            Self::Quantified(q) => Pattern::Sub(Self::Quantified(q.without_flag(flag))),
-            other => Pattern::Sub(self.clone()),
+            Self::Alternatives(alts) => alts.walk_paths(path, f),
+            Self::Quantified(q) => {
+                if let Quantifiable::Group(g) = &q.quantifiable {
+                    g.walk_paths(path, f);
+                }
+            }
+            _ => (),
+        }
+    }
+}
+
+/// The 64-bit FNV-1a hash, whose output, unlike `DefaultHasher`'s, is fixed
+struct Fnv1a(u64);
+
+impl Default for Fnv1a {
+    fn default() -> Self {
+        Self(0xcbf2_9ce4_8422_2325)
+    }
+}
+
+impl Hasher for Fnv1a {
+    fn write(&mut self, bytes: &[u8]) {
+        for b in bytes {
+            self.0 = (self.0 ^ u64::from(*b)).wrapping_mul(0x0100_0000_01b3);
         }
     }
+    fn finish(&self) -> u64 {
+        self.0
+    }
+}
 
-    fn as_pattern(&self) -> Pattern {
-        Pattern::Sub(self.clone())
+/// Write character sets and flags as they were parsed, so widths match the
+/// source as far as it was kept
+fn original_opts() -> EmitOptions {
+    EmitOptions {
+        prefer_original: true,
+        ..EmitOptions::default()
+    }
+}
+
+pub(crate) fn path_string(path: &[usize]) -> String {
+    path.iter()
+        .map(usize::to_string)
+        .collect::<Vec<String>>()
+        .join("/")
+}
+
+/// Every string from `a` followed by one from `b`, up to `max_len`
+/// characters, or `None` if there are more than `MAX_ENUMERATED_STRINGS`
+fn concat_strings(
+    a: &BTreeSet<String>,
+    b: &BTreeSet<String>,
+    max_len: usize,
+) -> Option<BTreeSet<String>> {
+    let mut out = BTreeSet::new();
+    for x in a {
+        let x_len = x.chars().count();
+        for y in b {
+            if x_len + y.chars().count() <= max_len {
+                out.insert(format!("{}{}", x, y));
+                if out.len() > MAX_ENUMERATED_STRINGS {
+                    return None;
+                }
+            }
+        }
+    }
+    Some(out)
+}
+
+/// Every total of one length from `a` and one from `b`
+pub(crate) fn sum_lengths(a: &BTreeSet<usize>, b: &BTreeSet<usize>) -> BTreeSet<usize> {
+    a.iter()
+        .flat_map(|x| b.iter().map(move |y| x + y))
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_to_regex_syntax_named_group() {
+        let p = Pattern::parse("(?'x'a)").unwrap();
+        assert_eq!(String::from("(?P<x>a)"), p.to_regex_syntax().unwrap());
+        let p = Pattern::parse("(?<x>a)").unwrap();
+        assert_eq!(String::from("(?P<x>a)"), p.to_regex_syntax().unwrap());
+    }
+    #[test]
+    fn test_backreference_check() {
+        assert!(
+            Pattern::parse("(a)\\1")
+                .unwrap()
+                .backreference_check()
+                .is_ok()
+        );
+        assert!(
+            Pattern::parse("(a)\\2")
+                .unwrap()
+                .backreference_check()
+                .is_err()
+        );
+        assert!(
+            Pattern::parse("(?P=missing)")
+                .unwrap()
+                .backreference_check()
+                .is_err()
+        );
+        assert!(
+            Pattern::parse("(?P<x>a)(?(x)b|c)")
+                .unwrap()
+                .backreference_check()
+                .is_ok()
+        );
+    }
+    #[test]
+    fn test_nth_group() {
+        let p = Pattern::parse("(a)(?:b(c))+").unwrap();
+        assert_eq!(String::from("(a)"), p.nth_group(1).unwrap().as_string());
+        assert_eq!(String::from("(c)"), p.nth_group(2).unwrap().as_string());
+        assert!(p.nth_group(3).is_none());
+    }
+    #[test]
+    fn test_inline_flags_to_group() {
+        let p = Pattern::parse("(?im)ab(c)")
+            .unwrap()
+            .inline_flags_to_group();
+        assert_eq!(String::from("(?im:ab(c))"), p.as_string());
+        assert_eq!(String::from("(c)"), p.nth_group(1).unwrap().as_string());
+    }
+    #[test]
+    fn test_widen_narrow_quantifier() {
+        let p = Pattern::parse("a{2,4}").unwrap();
+        assert_eq!(String::from("a{2,6}"), p.widen_quantifier(2).as_string());
+        assert_eq!(String::from("a{0,4}"), p.narrow_quantifier(2).as_string());
+        let p = Pattern::parse("a*").unwrap();
+        assert_eq!(String::from("a*"), p.widen_quantifier(2).as_string());
+    }
+    #[test]
+    fn test_quantifiers() {
+        let p = Pattern::parse("a+(b*|c)").unwrap();
+        let qs = p.quantifiers();
+        assert_eq!(2, qs.len());
+        assert_eq!(String::from("0"), qs[0].1);
+        assert_eq!(String::from("1/0/0"), qs[1].1);
+    }
+    #[test]
+    fn test_verify_alternation() {
+        let p = Pattern::new_alternatives(vec![
+            Pattern::new_literal("a".into(), None),
+            Pattern::new_literal("bc".into(), None),
+            Pattern::new_character_class(CClass::D, None),
+        ]);
+        assert!(p.verify().is_ok());
+    }
+    #[test]
+    fn test_verify_nested_group() {
+        let inner = Pattern::new_group(
+            vec![
+                Pattern::new_literal("b".into(), None),
+                Pattern::new_literal("c".into(), None),
+            ],
+            None,
+            Some("inner".into()),
+            None,
+        );
+        let p = Pattern::new_group(
+            vec![Pattern::new_literal("a".into(), None), inner],
+            None,
+            None,
+            Some(GroupExt::NonCapturing),
+        );
+        assert!(p.verify().is_ok());
+    }
+    #[test]
+    fn test_mid_pattern_set_flags() {
+        let Pattern::Pat(p) = Pattern::parse("a(?i)b").unwrap() else {
+            unreachable!()
+        };
+        assert_eq!(3, p.sub_patterns.len());
+        assert_eq!(
+            SubPattern::SetFlags(Flags::new_single(Flag::Ignorecase)),
+            p.sub_patterns[1]
+        );
+        assert_eq!(String::from("a(?i)b"), p.as_string());
+        assert_eq!(2, p.min_match_len());
+    }
+    #[test]
+    fn test_flag_is_noop() {
+        assert!(
+            Pattern::parse("\\d+")
+                .unwrap()
+                .flag_is_noop(Flag::Ignorecase)
+        );
+        assert!(
+            !Pattern::parse("abc")
+                .unwrap()
+                .flag_is_noop(Flag::Ignorecase)
+        );
+        assert!(Pattern::parse("abc").unwrap().flag_is_noop(Flag::Dotall));
+        assert!(!Pattern::parse("a.c").unwrap().flag_is_noop(Flag::Dotall));
+        assert!(
+            !Pattern::parse("^abc$")
+                .unwrap()
+                .flag_is_noop(Flag::Multiline)
+        );
+        assert!(
+            Pattern::parse("(?i)abc")
+                .unwrap()
+                .flag_is_noop(Flag::Ignorecase)
+        );
+    }
+    #[test]
+    fn test_remove_group_ext() {
+        let p = Pattern::parse("(?>ab)").unwrap();
+        assert_eq!(
+            String::from("(?:ab)"),
+            p.remove_group_ext(GroupExt::Atomic).unwrap().as_string()
+        );
+        let p = Pattern::parse("(?:a(?:b))(?i:c)").unwrap();
+        assert_eq!(
+            String::from("(a(b))(?i:c)"),
+            p.remove_group_ext(GroupExt::NonCapturing)
+                .unwrap()
+                .as_string()
+        );
+        assert!(p.remove_group_ext(GroupExt::PosLookahead).is_err());
+    }
+    #[test]
+    fn test_verbose_parse() {
+        let verbose = Pattern::parse("(?x) a b c  # letters").unwrap();
+        let plain = Pattern::parse("abc").unwrap();
+        assert!(verbose.flags().unwrap().contains(Flag::Verbose));
+        assert_eq!(plain.components(), verbose.components());
+        assert_eq!(String::from("abc"), verbose.as_string());
+    }
+    #[test]
+    fn test_redundant_nested_quantifiers() {
+        let p = Pattern::parse("x(?:a{2}){3}").unwrap();
+        assert_eq!(
+            vec![String::from("1: (?:a{2}){3} could be a{6}")],
+            p.redundant_nested_quantifiers()
+        );
+        let p = Pattern::parse("(?:a+){2}").unwrap();
+        assert!(p.redundant_nested_quantifiers().is_empty());
+    }
+    #[test]
+    fn test_escaped_slash_round_trip() {
+        let p = Pattern::parse("a\\/b").unwrap();
+        assert_eq!(String::from("a/b"), p.as_string());
+        assert_eq!(String::from("a\\/b"), p.as_delimited_string());
+        assert_eq!(p, Pattern::parse(&p.as_string()).unwrap());
+        assert!(p.verify().is_ok());
+        let p = Pattern::parse("[!-\\/]").unwrap();
+        assert!(p.char_sets()[0].contains('/'));
+        assert_eq!(String::from("[!-\\/]"), p.as_delimited_string());
+    }
+    #[test]
+    fn test_reverse() {
+        let p = Pattern::parse("ab(?=c)").unwrap();
+        assert_eq!(String::from("(?<=c)ba"), p.reverse().unwrap().as_string());
+        let p = Pattern::parse("abc").unwrap();
+        assert_eq!(String::from("cba"), p.reverse().unwrap().as_string());
+        let p = Pattern::parse("^(?:de|f)$").unwrap();
+        assert_eq!(String::from("^(?:ed|f)$"), p.reverse().unwrap().as_string());
+        assert!(Pattern::parse("(a)\\1").unwrap().reverse().is_err());
+        let p = Pattern::parse("a(?i)bc").unwrap();
+        assert_eq!(String::from("(?i:cb)a"), p.reverse().unwrap().as_string());
+        let p = Pattern::parse("(?:x(?s)y)").unwrap();
+        assert_eq!(
+            String::from("(?:(?s:y)x)"),
+            p.reverse().unwrap().as_string()
+        );
+    }
+    #[test]
+    fn test_ternary_condition_refs() {
+        let p = Pattern::parse("(?P<name>a)?(?(name)a|b)").unwrap();
+        assert_eq!(
+            vec![TernaryGroupId::Named(String::from("name"))],
+            p.ternary_condition_refs()
+        );
+        let p = Pattern::parse("(a)?(?(1)x)").unwrap();
+        assert_eq!(
+            vec![TernaryGroupId::Numbered(1)],
+            p.ternary_condition_refs()
+        );
+    }
+    #[test]
+    fn test_ambiguous_alternation() {
+        let p = Pattern::parse("ab|abc").unwrap();
+        assert_eq!(
+            vec![String::from("0: ab and abc can start the same way")],
+            p.ambiguous_alternation()
+        );
+        let p = Pattern::parse("ab|cd").unwrap();
+        assert!(p.ambiguous_alternation().is_empty());
+        let p = Pattern::parse("(?:x?a)|[a-c]").unwrap();
+        assert_eq!(1, p.ambiguous_alternation().len());
+    }
+    #[test]
+    fn test_unreachable_branches() {
+        let p = Pattern::parse(".*|x").unwrap();
+        assert_eq!(
+            vec![String::from("0: x is unreachable after .*")],
+            p.unreachable_branches()
+        );
+        assert!(
+            Pattern::parse(".+|x")
+                .unwrap()
+                .unreachable_branches()
+                .is_empty()
+        );
+        assert!(
+            Pattern::parse("x|.*")
+                .unwrap()
+                .unreachable_branches()
+                .is_empty()
+        );
+    }
+    #[test]
+    fn test_to_json() {
+        let json = Pattern::parse("a+").unwrap().to_json();
+        assert!(json.contains(
+            r#"{"type":"Quantified","quantifiable":{"type":"Literal","text":"a"},"quantifier":"OneOrMore","greed":"Greedy"}"#
+        ));
+        let json = Pattern::parse("(?P<q>\"[^\\d])").unwrap().to_json();
+        assert!(json.contains(r#""name":"q""#));
+        assert!(json.contains(r#""text":"\"""#));
+        assert!(json.contains(r#""shorthand":null"#));
+    }
+    #[test]
+    fn test_json_round_trip() {
+        let src =
+            "(?i)^(?P<word>[a-f\\d]+?)\\s{2,}(?:x|y\\b{g})*(?(word)z|\\1)(?#note)(*FAIL)(?=q)$";
+        let p = Pattern::parse(src).unwrap();
+        assert_eq!(p, Pattern::from_json(&p.to_json()).unwrap());
+        let p = Pattern::parse("(?s-m:a\"b{3})").unwrap();
+        assert_eq!(p, Pattern::from_json(&p.to_json()).unwrap());
+        let err = Pattern::from_json("{\"type\":\"Pat\"").unwrap_err();
+        assert!(matches!(
+            err.downcast_ref::<ReggieError>(),
+            Some(ReggieError::InvalidJson { .. })
+        ));
+        assert!(Pattern::from_json("{\"type\":\"Nope\"}").is_err());
+    }
+    #[test]
+    fn test_unused_captures() {
+        assert_eq!(
+            vec![2],
+            Pattern::parse("(a)(b)\\1").unwrap().unused_captures()
+        );
+        let p = Pattern::parse("(?P<x>a)(b)(c)(?(3)d)(?P=x)").unwrap();
+        assert_eq!(vec![2], p.unused_captures());
+        assert!(
+            Pattern::parse("a(?:b)")
+                .unwrap()
+                .unused_captures()
+                .is_empty()
+        );
+    }
+    #[test]
+    fn test_required_literals() {
+        let required = |src: &str| Pattern::parse(src).unwrap().required_literals();
+        assert_eq!(
+            vec![String::from("a"), String::from("d")],
+            required("a(b|c)d")
+        );
+        assert_eq!(vec![String::from("y")], required("x?y"));
+        assert_eq!(vec![String::from("b")], required("(?:b)+(?=c)(?:d)*"));
+        assert_eq!(vec![String::from("ab")], required("abc?"));
+    }
+    #[test]
+    fn test_is_end_anchored() {
+        let anchored = |src: &str| Pattern::parse(src).unwrap().is_end_anchored();
+        assert!(anchored("abc\\z"));
+        assert!(!anchored("abc"));
+        assert!(anchored("(a\\z|b\\z)"));
+        assert!(anchored("(?:x$|y\\z)(?#done)"));
+        assert!(!anchored("(?:x$|y)"));
+    }
+    #[test]
+    fn test_distinct_length_count() {
+        let count = |src: &str| Pattern::parse(src).unwrap().distinct_length_count();
+        assert_eq!(Some(3), count("ab|cde|f"));
+        assert_eq!(None, count("a+"));
+        assert_eq!(Some(3), count("(?:ab){1,3}"));
+        assert_eq!(Some(2), count("x(?:y|zz)"));
+        assert_eq!(
+            Some(BTreeSet::from([3])),
+            Pattern::parse("ab{2}").unwrap().possible_lengths()
+        );
+        assert_eq!(
+            Some(BTreeSet::from([2, 3])),
+            Pattern::parse("abc?").unwrap().possible_lengths()
+        );
+    }
+    #[test]
+    fn test_to_alternation_normal_form() {
+        let anf = |src: &str| {
+            Pattern::parse(src)
+                .unwrap()
+                .to_alternation_normal_form()
+                .as_string()
+        };
+        assert_eq!(String::from("ac|ad|bc|bd"), anf("(a|b)(c|d)"));
+        assert_eq!(String::from("ac|bc"), anf("(?:a|b)c"));
+        assert_eq!(String::from("(a|b)\\1"), anf("(a|b)\\1"));
+        assert_eq!(String::from("(?:a|b)+"), anf("(?:a|b)+"));
+        let many = "(a|b)".repeat(9);
+        assert_eq!(many, anf(&many));
+    }
+    #[test]
+    fn test_error_node() {
+        let err = SubPattern::Error {
+            raw: String::from("(?<x"),
+            span: (1, 5),
+        };
+        let p = Pattern::Pat(Pat {
+            flags: Flags::empty(),
+            sub_patterns: vec![SubPattern::new_literal(String::from("a"), None), err],
+        });
+        assert_eq!(String::from("a(?<x"), p.as_string());
+        assert_eq!(p, Pattern::from_json(&p.to_json()).unwrap());
+        assert_eq!((1, Some(1)), p.length_range());
+        assert_eq!(0, p.groups_count());
+        assert!(p.is_finite());
+    }
+    #[test]
+    fn test_factor_common_prefix() {
+        let factored = |src: &str| {
+            Pattern::parse(src)
+                .unwrap()
+                .factor_common_prefix()
+                .as_string()
+        };
+        assert_eq!(String::from("ab(?:c|d)"), factored("abc|abd"));
+        assert_eq!(String::from("abc|xyz"), factored("abc|xyz"));
+        assert_eq!(String::from("foo(?:(?:)|t|l)"), factored("foo|foot|fool"));
+        assert_eq!(String::from("a[b](?:c|d)"), factored("(?:a[b]c)|(?:a[b]d)"));
+        assert!(Pattern::parse(&factored("foo|foot|fool")).is_ok());
+    }
+    #[test]
+    fn test_factor_common_suffix() {
+        let factored = |src: &str| {
+            Pattern::parse(src)
+                .unwrap()
+                .factor_common_suffix()
+                .as_string()
+        };
+        assert_eq!(String::from("(?:c|b)at"), factored("cat|bat"));
+        assert_eq!(String::from("(?:c|b|h)at"), factored("cat|bat|hat"));
+        assert_eq!(String::from("cat|dog"), factored("cat|dog"));
+        assert_eq!(
+            String::from("(?:x|yz)[0-9]q"),
+            factored("(?:x[0-9]q)|(?:yz[0-9]q)")
+        );
+    }
+    #[test]
+    fn test_validate_group_names() {
+        let err = Pattern::parse("(?P<1bad>a)")
+            .unwrap()
+            .validate_group_names()
+            .unwrap_err();
+        assert!(matches!(
+            err.downcast_ref::<ReggieError>(),
+            Some(ReggieError::InvalidGroupName { name }) if name == "1bad"
+        ));
+        assert!(
+            Pattern::parse("(?P<good_1>a)(?P=good_1)")
+                .unwrap()
+                .validate_group_names()
+                .is_ok()
+        );
+    }
+    #[test]
+    fn test_prefer_shorthands() {
+        let p = Pattern::parse("[^0-9]+[0-9A-Za-z]x[a-c]").unwrap();
+        assert_eq!(
+            String::from("\\D+\\wx[a-c]"),
+            p.prefer_shorthands().as_string()
+        );
+    }
+    #[test]
+    fn test_ascii_first_byte_set() {
+        let bytes = Pattern::parse("[a-c]x")
+            .unwrap()
+            .ascii_first_byte_set()
+            .unwrap();
+        let starts: Vec<u8> = (0..=255u8).filter(|b| bytes[usize::from(*b)]).collect();
+        assert_eq!(b"abc".to_vec(), starts);
+        let bytes = Pattern::parse("(?i)q")
+            .unwrap()
+            .ascii_first_byte_set()
+            .unwrap();
+        assert!(bytes[usize::from(b'Q')] && bytes[usize::from(b'q')]);
+        assert_eq!(None, Pattern::parse("a?b?").unwrap().ascii_first_byte_set());
+        assert_eq!(None, Pattern::parse(".").unwrap().ascii_first_byte_set());
+        let bytes = Pattern::parse("abc?")
+            .unwrap()
+            .ascii_first_byte_set()
+            .unwrap();
+        let starts: Vec<u8> = (0..=255u8).filter(|b| bytes[usize::from(*b)]).collect();
+        assert_eq!(b"a".to_vec(), starts);
+    }
+    #[test]
+    fn test_fixed_width() {
+        let width = |src: &str| Pattern::parse(src).unwrap().fixed_width();
+        assert_eq!(Some(3), width("ab{2}"));
+        assert_eq!(Some(4), width("(?:ab){2}"));
+        assert_eq!(None, width("abc?"));
+    }
+    #[test]
+    fn test_branches_by_length() {
+        let by_length = Pattern::parse("ab|cd|efg")
+            .unwrap()
+            .branches_by_length()
+            .unwrap();
+        let as_strings: Vec<(usize, Vec<String>)> = by_length
+            .iter()
+            .map(|(n, branches)| (*n, branches.iter().map(Pattern::as_string).collect()))
+            .collect();
+        assert_eq!(
+            vec![
+                (2, vec![String::from("ab"), String::from("cd")]),
+                (3, vec![String::from("efg")])
+            ],
+            as_strings
+        );
+        assert_eq!(None, Pattern::parse("ab|c+").unwrap().branches_by_length());
+    }
+    #[test]
+    fn test_explain() {
+        let explain = |src: &str| Pattern::parse(src).unwrap().explain();
+        assert_eq!("one or more digits", explain("\\d+"));
+        assert_eq!("a character in [a-z]", explain("[a-z]"));
+        assert_eq!("either 'a' or 'b'", explain("(?:a|b)"));
+        assert_eq!("between 2 and 4 digits", explain("\\d{2,4}"));
+        assert_eq!("the text 'abc', case-insensitively", explain("(?i)abc"));
+        assert_eq!("either 'a' or 'b' (captured as group 1)", explain("(a|b)"));
+        assert_eq!(
+            "the start of the input, then optionally any character (as few as possible)",
+            explain("\\A.??")
+        );
+    }
+    #[test]
+    fn test_collapse_repeated_elements() {
+        let collapsed = |src: &str| {
+            Pattern::parse(src)
+                .unwrap()
+                .collapse_repeated_elements()
+                .as_string()
+        };
+        assert_eq!(String::from("[a-z]{3}"), collapsed("[a-z][a-z][a-z]"));
+        assert_eq!(String::from("[a-z][0-9]"), collapsed("[a-z][0-9]"));
+        assert_eq!(
+            String::from("x(?:\\d{2}y)\\d+\\d"),
+            collapsed("x(?:\\d\\dy)\\d+\\d")
+        );
+    }
+    #[test]
+    fn test_bound_lazy_quantifiers() {
+        let bounded = |src: &str| {
+            Pattern::parse(src)
+                .unwrap()
+                .bound_lazy_quantifiers(3)
+                .as_string()
+        };
+        assert_eq!(String::from("a{0,3}"), bounded("a*?"));
+        assert_eq!(String::from("(?:ab){1,3}c*"), bounded("(?:ab)+?c*"));
+        assert_eq!(String::from("a{5,5}b??"), bounded("a{5,}?b??"));
+    }
+    #[test]
+    fn test_comments() {
+        let p = Pattern::parse("a(?#first)b(?#second)").unwrap();
+        assert_eq!(vec!["first", "second"], p.comments());
+        let p = Pattern::parse("(?:x(?#inner)|y)").unwrap();
+        assert_eq!(vec!["inner"], p.comments());
+    }
+    #[test]
+    fn test_node_count() {
+        assert_eq!(11, Pattern::parse("a(b|c)").unwrap().node_count());
+        assert_eq!(3, Pattern::parse("abc").unwrap().node_count());
+        assert_eq!(2, Pattern::parse("(?#x)").unwrap().node_count());
+    }
+    #[test]
+    fn test_empty_loop_risk() {
+        let risky = |src: &str| !Pattern::parse(src).unwrap().empty_loop_risk().is_empty();
+        assert!(risky("(a?)*"));
+        assert!(risky("(a*)+"));
+        assert!(!risky("(a)+"));
+        assert_eq!(
+            vec![String::from(
+                "1/0: (?:b*)+ repeats something that can match nothing"
+            )],
+            Pattern::parse("a((?:b*)+)").unwrap().empty_loop_risk()
+        );
+    }
+    #[test]
+    fn test_unbounded_literals() {
+        let unbounded = |src: &str| Pattern::parse(src).unwrap().unbounded_literals();
+        assert_eq!(vec!["b", "c"], unbounded("ab+c*"));
+        assert!(unbounded("abc").is_empty());
+        assert_eq!(vec!["x"], unbounded("x{2,}y{2,3}z?"));
+        assert_eq!(vec!["ab"], unbounded("(ab)+"));
+        assert_eq!(vec!["x", "y"], unbounded("(?:x|y)*"));
+        assert_eq!(vec!["a", "bc"], unbounded("(?:a(bc)?)+"));
+        assert!(unbounded("(?:a(b)){1,3}").is_empty());
+    }
+    #[test]
+    fn test_quantified_zero_width() {
+        let flagged = |src: &str| Pattern::parse(src).unwrap().quantified_zero_width();
+        assert_eq!(
+            vec![String::from("0: (?=a)* quantifies something zero-width")],
+            flagged("(?=a)*")
+        );
+        assert!(flagged("a+").is_empty());
+        assert_eq!(1, flagged("x(?:\\b(?!y))+").len());
+    }
+    #[test]
+    fn test_max_group_number() {
+        assert_eq!(2, Pattern::parse("(a)(b)").unwrap().max_group_number());
+        assert_eq!(
+            3,
+            Pattern::parse("((a)|(?:b))(?P<c>c)")
+                .unwrap()
+                .max_group_number()
+        );
+        assert_eq!(0, Pattern::parse("(?:a)(?=b)").unwrap().max_group_number());
+        assert!(Pattern::parse("(?|(a)|(b))").is_err());
+    }
+    #[test]
+    fn test_content_hash() {
+        let hash = |src: &str| Pattern::parse(src).unwrap().content_hash();
+        assert_eq!(hash("[a-c]"), hash("[abc]"));
+        assert_eq!(hash("\\d+"), hash("[0-9]+"));
+        assert_eq!(hash("(?x)a b"), hash("ab"));
+        assert_ne!(hash("[a-c]"), hash("[a-d]"));
+        assert_ne!(hash("a+"), hash("a*"));
+    }
+    #[test]
+    fn test_broad_char_sets() {
+        let p = Pattern::parse("[^a]x[a-z]").unwrap();
+        let broad = p.broad_char_sets(1000);
+        assert_eq!(vec![p.char_sets()[0].as_string()], broad);
+        assert!(p.broad_char_sets(usize::MAX).is_empty());
+    }
+    #[test]
+    fn test_flags_at() {
+        let p = Pattern::parse("(?i)a(?-i:b)c").unwrap();
+        assert_eq!(String::from("(?i)a(?-i:b)c"), p.as_string());
+        assert!(p.flags_at(4).contains(Flag::Ignorecase));
+        assert!(!p.flags_at(10).contains(Flag::Ignorecase));
+        assert!(p.flags_at(12).contains(Flag::Ignorecase));
+        let p = Pattern::parse("a(?s:b|(?m:c))").unwrap();
+        assert!(p.flags_at(0).is_empty());
+        let at_c = p.flags_at(11);
+        assert!(at_c.contains(Flag::Dotall) && at_c.contains(Flag::Multiline));
+    }
+    #[test]
+    fn test_enumerate() {
+        let enumerated =
+            |src: &str, max_len: usize| Pattern::parse(src).unwrap().enumerate(max_len);
+        assert_eq!(
+            vec!["a", "aa", "ab", "b", "ba", "bb"],
+            enumerated("[ab]{1,2}", 5).unwrap()
+        );
+        assert_eq!(vec!["", "x", "xx"], enumerated("x*", 2).unwrap());
+        assert_eq!(vec!["abb"], enumerated("ab{2}", 3).unwrap());
+        assert_eq!(vec!["ab", "abb", "abbb"], enumerated("ab+", 4).unwrap());
+        assert_eq!(
+            vec!["a", "ac", "b", "bc"],
+            enumerated("(?:a|b)c?", 2).unwrap()
+        );
+        assert!(enumerated("[^a]", 1).is_err());
+        assert!(enumerated("(a)\\1", 2).is_err());
+        assert!(enumerated("[a-z]{0,8}", 8).is_err());
+    }
+    #[test]
+    fn test_matches_everything() {
+        let universal = |src: &str| Pattern::parse(src).unwrap().matches_everything();
+        assert!(universal("(?s).*"));
+        assert!(universal("(?s:.*)"));
+        assert!(!universal(".*"));
+        assert!(!universal("(?s)(?-s:.*)"));
+        assert!(universal("[\\x00-\\u{10ffff}]*"));
+        assert!(universal("(?:[\\s\\S]*)(?#anything)"));
+        assert!(!universal("a.*"));
+        assert!(!universal(".+"));
+        assert!(!universal("[^a]*"));
+    }
+    #[test]
+    fn test_is_prefix_of() {
+        let prefix = |a: &str, b: &str| {
+            Pattern::parse(a)
+                .unwrap()
+                .is_prefix_of(&Pattern::parse(b).unwrap())
+        };
+        assert!(prefix("ab", "abc"));
+        assert!(!prefix("ac", "abc"));
+        assert!(prefix("a[0-9]+", "a[0-9]+b"));
+        assert!(!prefix("a[0-9]", "a[0-9]+b"));
+        assert!(!prefix("(?i)a", "ab"));
+    }
+    #[test]
+    fn test_relative_backrefs() {
+        let p = Pattern::parse("(a)(b)\\k<-1>").unwrap();
+        assert_eq!(vec![Some(2)], p.relative_backref_targets());
+        assert_eq!(String::from("(a)(b)\\k<-1>"), p.as_string());
+        assert!(p.backreference_check().is_ok());
+        assert_eq!(vec![1], p.unused_captures());
+        let p = Pattern::parse("\\k<+1>+(a)").unwrap();
+        assert_eq!(vec![Some(1)], p.relative_backref_targets());
+        assert_eq!(String::from("\\k<+1>+(a)"), p.as_string());
+        assert_eq!(p, Pattern::from_json(&p.to_json()).unwrap());
+        let err = Pattern::parse("(a)\\k<-2>")
+            .unwrap()
+            .backreference_check()
+            .unwrap_err();
+        assert!(matches!(
+            err.downcast_ref::<ReggieError>(),
+            Some(ReggieError::UndefinedBackreference { reference }) if reference == "\\k<-2>"
+        ));
+    }
+    #[test]
+    fn test_quantify_group() {
+        let p = Pattern::parse("(ab)(cd)").unwrap();
+        assert_eq!(
+            String::from("(ab)+(cd)"),
+            p.quantify_group(1, Quantifier::plus()).unwrap().as_string()
+        );
+        assert_eq!(
+            String::from("((a)?)"),
+            Pattern::parse("((a))")
+                .unwrap()
+                .quantify_group(2, Quantifier::optional())
+                .unwrap()
+                .as_string()
+        );
+        let err = p.quantify_group(3, Quantifier::plus()).unwrap_err();
+        assert!(matches!(
+            err.downcast_ref::<ReggieError>(),
+            Some(ReggieError::NoSuchGroup { number: 3 })
+        ));
+        assert!(p.quantify_group(0, Quantifier::plus()).is_err());
+    }
+    #[test]
+    fn test_must_match_at_start() {
+        let at_start = |src: &str| Pattern::parse(src).unwrap().must_match_at_start();
+        assert!(at_start("\\Aab"));
+        assert!(!at_start("(?m)^ab"));
+        assert!(at_start("^ab"));
+        assert!(!at_start("ab"));
+        assert!(at_start("(?:^a|\\Ab)"));
+        assert!(!at_start("(?m:^a)"));
+        assert!(at_start("(?m)(?-m:^a)"));
+    }
+    #[test]
+    fn test_named_groups_ordered() {
+        let p = Pattern::parse("(?P<b>x)(?P<a>y)").unwrap();
+        let names: Vec<String> = p
+            .named_groups_ordered()
+            .into_iter()
+            .map(|(name, _)| name)
+            .collect();
+        assert_eq!(vec![String::from("b"), String::from("a")], names);
+    }
+    #[test]
+    fn test_minimize_captures() {
+        let p = Pattern::parse("(a)(b)\\1").unwrap().minimize_captures();
+        assert_eq!(String::from("(a)(?:b)\\1"), p.as_string());
+        assert!(p.backreference_check().is_ok());
+        let p = Pattern::parse("(a)(b(c))(?(3)d)\\3")
+            .unwrap()
+            .minimize_captures();
+        assert_eq!(String::from("(?:a)(?:b(c))(?(1)d)\\1"), p.as_string());
+        assert!(p.unused_captures().is_empty());
+    }
+    #[test]
+    fn test_normalize_quantifier_spelling() {
+        for (src, expected) in [
+            ("a{1}", "a"),
+            ("a{0,1}", "a?"),
+            ("a{1,}", "a+"),
+            ("a{0,}", "a*"),
+            ("a{0,1}?", "a??"),
+            ("(ab){1,}+", "(ab)++"),
+            ("a{2,3}", "a{2,3}"),
+        ] {
+            let p = Pattern::parse(src).unwrap();
+            assert_eq!(
+                String::from(expected),
+                p.normalize_quantifier_spelling().as_string()
+            );
+        }
+    }
+    #[test]
+    fn test_tokens() {
+        let tokens = Pattern::parse("(a)+").unwrap().tokens();
+        assert_eq!(
+            vec![
+                Token::GroupOpen(String::from("(")),
+                Token::Literal(String::from("a")),
+                Token::GroupClose(String::from(")")),
+                Token::Quantifier(String::from("+")),
+            ],
+            tokens
+        );
+        let p = Pattern::parse("(?i)^(?P<n>[a-c\\d]|\\w)(?#hi)(?(n)x|y)\\1.*?$").unwrap();
+        let joined: String = p.tokens().iter().map(Token::text).collect();
+        assert_eq!(p.as_string(), joined);
+    }
+    #[test]
+    fn test_matches_only_empty() {
+        for src in ["(?:)", "()", "\\A\\z", "\\b", "(?=a)"] {
+            assert!(Pattern::parse(src).unwrap().matches_only_empty(), "{}", src);
+        }
+        for src in ["a*", "a", "(?:a?)", "()\\1"] {
+            assert!(
+                !Pattern::parse(src).unwrap().matches_only_empty(),
+                "{}",
+                src
+            );
+        }
+    }
+    #[test]
+    fn test_as_string_original() {
+        let p = Pattern::parse("[abc]").unwrap();
+        assert_eq!(String::from("[abc]"), p.as_string_original());
+        assert_eq!(String::from("[a-c]"), p.as_string());
+        let p = Pattern::parse("(?mi)x(?si-m:[^a]y)").unwrap();
+        assert_eq!(String::from("(?mi)x(?si-m:[^a]y)"), p.as_string_original());
+        assert_eq!(p, Pattern::parse(&p.as_string()).unwrap());
+        let built = Pattern::parse("[abc]")
+            .unwrap()
+            .transform(&mut |sp| match sp {
+                SubPattern::Quantified(Quantified {
+                    quantifiable: Quantifiable::Element(Element::CharSet(cs)),
+                    quantifier,
+                }) => SubPattern::Quantified(Quantified {
+                    quantifiable: Quantifiable::Element(Element::CharSet(cs.complement())),
+                    quantifier,
+                }),
+                other => other,
+            });
+        assert_eq!(built.as_string(), built.as_string_original());
+    }
+    #[test]
+    fn test_group_is_finite() {
+        for (src, finite) in [
+            ("(a+)", false),
+            ("(?>a*)", false),
+            ("(a){2}", true),
+            ("(?:b|(c*))", false),
+            ("(?P<x>a)(?P=x)", true),
+        ] {
+            assert_eq!(finite, Pattern::parse(src).unwrap().is_finite(), "{}", src);
+        }
+    }
+    #[test]
+    fn test_length_range() {
+        let p = Pattern::parse("a{2,4}").unwrap();
+        assert_eq!((2, Some(4)), p.length_range());
+        assert!(p.could_match_length(3));
+        assert!(!p.could_match_length(5));
+        assert!(!p.could_match_length(1));
+        let p = Pattern::parse("\\u{e9}\\u{e8}").unwrap();
+        assert_eq!((2, Some(2)), p.length_range());
+        let p = Pattern::parse("\\u{e9}+").unwrap();
+        assert_eq!((1, None), p.length_range());
+        assert!(p.could_match_length(100));
+    }
+    #[test]
+    fn test_literal_escaped() {
+        let p = Pattern::literal_escaped("a.b", None);
+        assert_eq!(String::from("a\\.b"), p.as_string());
+        let reparsed = Pattern::parse(&p.as_string()).unwrap();
+        assert_eq!(Some(String::from("a.b")), reparsed.as_plain_literal());
+        let p = Pattern::literal_escaped("$", Some(Quantifier::plus()));
+        assert_eq!(String::from("\\$+"), p.as_string());
+    }
+    #[test]
+    fn test_group_ext_kinds() {
+        let p = Pattern::parse("(?=a)(?:b)(?<!c)").unwrap();
+        assert_eq!(
+            HashSet::from([
+                GroupExt::PosLookahead,
+                GroupExt::NonCapturing,
+                GroupExt::NegLookbehind
+            ]),
+            p.group_ext_kinds()
+        );
+        assert!(Pattern::parse("(a)b").unwrap().group_ext_kinds().is_empty());
+    }
+    #[test]
+    fn test_skeleton() {
+        let p = Pattern::parse("a+b*").unwrap();
+        assert_eq!(Pattern::parse("ab").unwrap().skeleton(), p.skeleton());
+        let p = Pattern::parse("a+b{2,4}(cd)*").unwrap();
+        assert_eq!(String::from("ab(cd)"), p.skeleton().as_string());
+    }
+    #[test]
+    fn test_verbose_escaped_space_and_hash() {
+        let p = Pattern::parse("(?x) a\\ b \\#c # trailing").unwrap();
+        assert_eq!(String::from("a b#c"), p.as_string());
+        assert!(p.verify().is_ok());
+        let plain = Pattern::parse("a\\ b\\#c").unwrap();
+        assert_eq!(String::from("a b#c"), plain.as_string());
+    }
+    #[test]
+    fn test_alternation_parts() {
+        let p = Pattern::parse("a|b|c").unwrap();
+        let parts = p.alternation_parts();
+        assert_eq!(3, parts.len());
+        assert_eq!(String::from("b"), parts[1].as_string());
+        let rejoined = Pattern::from_alternation(parts);
+        assert_eq!(p, rejoined);
+        assert_eq!(String::from("a|b|c"), rejoined.as_string());
+        let p = Pattern::parse("ab").unwrap();
+        assert_eq!(vec![p.clone()], p.alternation_parts());
+    }
+    #[test]
+    fn test_complexity_metrics() {
+        let p = Pattern::parse("(a+)+").unwrap();
+        assert_eq!(2, p.depth());
+        assert_eq!(2, p.star_height());
+        assert_eq!(1, p.capture_count());
+        let q = Pattern::parse("(?:a{3})*(?P<x>b)").unwrap();
+        assert_eq!(1, q.star_height());
+        assert_eq!(1, q.capture_count());
+    }
+    #[test]
+    fn test_max_nested_unbounded_quantifiers() {
+        let depth = |src| {
+            Pattern::parse(src)
+                .unwrap()
+                .max_nested_unbounded_quantifiers()
+        };
+        assert_eq!(2, depth("(a*)*"));
+        assert_eq!(3, depth("((a*)*)*"));
+        assert_eq!(1, depth("(a{3})*"));
+        assert_eq!(0, depth("(a{3}){2}"));
+    }
+    #[test]
+    fn test_complexity_score() {
+        let nested = Pattern::parse("(a+)+").unwrap();
+        let plain = Pattern::parse("abc").unwrap();
+        assert!(nested.complexity_score() > plain.complexity_score());
+        assert_eq!(nested.complexity_score(), nested.complexity_score());
+        assert_eq!(
+            nested.complexity_score(),
+            Pattern::parse("(a+)+").unwrap().complexity_score()
+        );
+    }
+    #[test]
+    fn test_as_plain_literal() {
+        let p = Pattern::parse("abc").unwrap();
+        assert_eq!(Some(String::from("abc")), p.as_plain_literal());
+        assert_eq!(None, Pattern::parse("a+").unwrap().as_plain_literal());
+        assert_eq!(None, Pattern::parse("(?i)abc").unwrap().as_plain_literal());
+        assert_eq!(
+            Some(String::from("12")),
+            Pattern::parse("(?i)^12$").unwrap().as_plain_literal()
+        );
+        assert_eq!(
+            None,
+            Pattern::parse("\\bfoo\\b").unwrap().as_plain_literal()
+        );
+        assert_eq!(None, Pattern::parse("foo\\B").unwrap().as_plain_literal());
+    }
+    #[test]
+    fn test_concat_preserving_flags() {
+        let i = Pattern::parse("(?i)a").unwrap();
+        let m = Pattern::parse("(?m)b").unwrap();
+        let joined = i.concat_preserving_flags(&m).unwrap();
+        let flags = joined.flags().unwrap();
+        assert!(flags.contains(Flag::Ignorecase));
+        assert!(flags.contains(Flag::Multiline));
+        assert_eq!(String::from("(?im)ab"), joined.as_string());
+        let plain = Pattern::parse("a")
+            .unwrap()
+            .concat_preserving_flags(&Pattern::parse("b").unwrap())
+            .unwrap();
+        assert_eq!(String::from("ab"), plain.as_string());
+        let a = Pattern::parse("(?a)x").unwrap();
+        let u = Pattern::parse("(?u)y").unwrap();
+        assert!(a.concat_preserving_flags(&u).is_err());
+    }
+    #[test]
+    fn test_to_string_escaped() {
+        for lit in ["a.b", "a*", "(c", "d #e"] {
+            let p = Pattern::new_literal(String::from(lit), None);
+            let reparsed = Pattern::parse(&p.to_string_escaped()).unwrap();
+            assert_eq!(Some(String::from(lit)), reparsed.as_plain_literal());
+            let verbose = Pattern::parse(&format!("(?x){}", p.to_string_escaped())).unwrap();
+            assert_eq!(Some(String::from(lit)), verbose.as_plain_literal());
+        }
+        let p = Pattern::parse("a\\.b\\(c").unwrap();
+        assert_eq!(String::from("a\\.b\\(c"), p.as_string());
+        let p = Pattern::new_character_set(vec![('-', '-'), (']', ']'), ('a', 'c')], None).unwrap();
+        assert_eq!(String::from("[\\-\\]a-c]"), p.to_string_escaped());
+        let reparsed = Pattern::parse(&p.to_string_escaped()).unwrap();
+        let cs = reparsed.char_sets()[0];
+        assert!(cs.contains('-') && cs.contains(']') && cs.contains('b'));
+        assert!(!cs.contains('\\'));
+    }
+    #[test]
+    fn test_element_at() {
+        let p = Pattern::parse("a[0-9]b").unwrap();
+        let Some(ElementRef::CharSet(cs)) = p.element_at(3) else {
+            panic!("expected a char set")
+        };
+        assert!(cs.contains('5'));
+        assert!(matches!(p.element_at(0), Some(ElementRef::Literal(_))));
+        assert!(matches!(p.element_at(6), Some(ElementRef::Literal(_))));
+        assert_eq!(None, p.element_at(7));
+        let p = Pattern::parse("(?i)x(?:yz+|[q])").unwrap();
+        let Some(ElementRef::Literal(l)) = p.element_at(9) else {
+            panic!("expected a literal")
+        };
+        assert_eq!("z", l.text());
+        assert!(matches!(p.element_at(13), Some(ElementRef::CharSet(_))));
+        assert_eq!(None, p.element_at(11));
+        // sets are measured as written, though `as_string` gives `[a-b]x`
+        let p = Pattern::parse("[ab]x").unwrap();
+        assert!(matches!(p.element_at(3), Some(ElementRef::CharSet(_))));
+        let Some(ElementRef::Literal(l)) = p.element_at(4) else {
+            panic!("expected a literal")
+        };
+        assert_eq!("x", l.text());
+        // but other spellings aren't: `\x61` is measured as `a`
+        let p = Pattern::parse("\\x61b[c]").unwrap();
+        assert!(matches!(p.element_at(2), Some(ElementRef::CharSet(_))));
+        assert_eq!(None, p.element_at(5));
+    }
+    #[test]
+    fn test_char_sets() {
+        let p = Pattern::parse("[a-z]\\d+[0-9]").unwrap();
+        let sets = p.char_sets();
+        assert_eq!(3, sets.len());
+        assert!(sets[0].contains('q'));
+        assert!(sets[1].contains('5'));
+    }
+    #[test]
+    fn test_remove_comments() {
+        let p = Pattern::parse("(?x) a (?#c) b").unwrap();
+        let expected = Pattern::parse("(?x)ab").unwrap();
+        assert_eq!(expected.normalized(), p.remove_comments().normalized());
+        assert_eq!(String::from("ab"), p.remove_comments().as_string());
+        let p = Pattern::parse("(a)\\1(?#c)0|(?#d)").unwrap();
+        assert_eq!(
+            String::from("(a)\\1(?:)0|(?:)"),
+            p.remove_comments().as_string()
+        );
+    }
+    #[test]
+    fn test_contains_subpattern() {
+        let p = Pattern::parse("a(bc)d").unwrap();
+        assert!(p.contains_subpattern(&Pattern::parse("bc").unwrap()));
+        assert!(p.contains_subpattern(&Pattern::parse("(bc)d").unwrap()));
+        assert!(!p.contains_subpattern(&Pattern::parse("bd").unwrap()));
+    }
+    #[test]
+    fn test_complement_simple() {
+        let p = Pattern::parse("[a-z]")
+            .unwrap()
+            .complement_simple()
+            .unwrap();
+        let Pattern::Pat(Pat { sub_patterns, .. }) = p else {
+            unreachable!()
+        };
+        let SubPattern::Quantified(Quantified {
+            quantifiable: Quantifiable::Element(Element::CharSet(cs)),
+            ..
+        }) = &sub_patterns[0]
+        else {
+            unreachable!()
+        };
+        assert!(cs.contains('0'));
+        assert!(!cs.contains('a'));
+        assert_eq!(
+            String::from("\\D+"),
+            Pattern::parse("\\d+")
+                .unwrap()
+                .complement_simple()
+                .unwrap()
+                .as_string()
+        );
+        assert!(Pattern::parse("ab").unwrap().complement_simple().is_err());
+    }
+    #[test]
+    fn test_to_regex_syntax_named_backref() {
+        let p = Pattern::parse("(?P<x>a)(?P=x)").unwrap();
+        assert!(p.to_regex_syntax().is_err());
+        let p = Pattern::parse("(a)\\1").unwrap();
+        assert!(p.to_regex_syntax().is_err());
     }
 }
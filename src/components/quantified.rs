@@ -1,37 +1,72 @@
 use crate::{
     components::{
-        CClass, CharSet, Element, Flags, Group, GroupExt, Quantifier,
+        CClass, CharSet, Element, EmitOptions, Flags, Group, GroupExt, Json, Quantifier, Token,
+        char_set::decode_char,
         flags::Flag,
-        pattern::{Pattern, SubPattern},
+        pattern::{SubPattern, sum_lengths},
     },
     error::ReggieError,
     parser::Rule,
 };
 use anyhow::Result;
 use pest::iterators::{Pair, Pairs};
+use std::collections::BTreeSet;
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, PartialEq, Hash)]
 pub enum Quantifiable {
     Element(Element),
     Group(Group),
 }
 
 impl Quantifiable {
+    pub(crate) fn from_json_value(json: &Json) -> Result<Self> {
+        match json.type_tag()? {
+            "CharSet" | "Literal" | "AnyChar" => Ok(Self::Element(Element::from_json_value(json)?)),
+            _ => Ok(Self::Group(Group::from_json_value(json)?)),
+        }
+    }
+    pub(crate) fn to_json_value(&self) -> Json {
+        match self {
+            Self::Element(e) => e.to_json_value(),
+            Self::Group(g) => g.to_json_value(),
+        }
+    }
     pub fn as_string(&self) -> String {
+        self.as_string_with(&EmitOptions::default())
+    }
+    pub fn as_string_with(&self, opts: &EmitOptions) -> String {
         match self {
-            Quantifiable::Element(e) => e.as_string(),
-            Quantifiable::Group(g) => g.as_string(),
+            Quantifiable::Element(e) => e.as_string_with(opts),
+            Quantifiable::Group(g) => g.as_string_with(opts),
         }
     }
-    fn min_match_len(&self) -> usize {
+    pub(crate) fn min_match_len(&self) -> usize {
         match self {
             Self::Element(e) => e.min_match_len(),
             Self::Group(g) => g.min_match_len(),
         }
     }
+    pub(crate) fn max_match_len(&self) -> Option<usize> {
+        match self {
+            Self::Element(e) => Some(e.max_match_len()),
+            Self::Group(g) => g.max_match_len(),
+        }
+    }
+    fn possible_lengths(&self) -> Option<BTreeSet<usize>> {
+        match self {
+            Self::Element(e) => Some(BTreeSet::from([e.max_match_len()])),
+            Self::Group(g) => g.possible_lengths(),
+        }
+    }
+    fn fixed_width(&self) -> Option<usize> {
+        match self {
+            Self::Element(e) => e.fixed_width(),
+            Self::Group(g) => g.fixed_width(),
+        }
+    }
 }
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, PartialEq, Hash)]
 pub struct Quantified {
     pub(crate) quantifiable: Quantifiable,
     pub(crate) quantifier: Option<Quantifier>,
@@ -43,6 +78,14 @@ impl Quantified {
             Rule::char_set => Quantifiable::Element(Element::charset_from_pair(pair)?),
             Rule::literals => Quantifiable::Element(Element::literals_from_pair(pair)?),
             Rule::group => Quantifiable::Group(Group::from_pair(pair)?),
+            Rule::relative_backref => Quantifiable::Group(Group::relative_backref_from_pair(pair)?),
+            Rule::any_char => Quantifiable::Element(Element::AnyChar),
+            Rule::code_point => Quantifiable::Element(Element::new_literal(String::from(
+                decode_char(pair.as_str())?,
+            ))),
+            Rule::char_class => Quantifiable::Element(Element::CharSet(CharSet::from_cclass(
+                CClass::from_str(pair.as_str())?,
+            ))),
             other => {
                 println!("quantified from_pair actually {:?}", other);
                 return Err(ReggieError::unexpected_input(pair).into());
@@ -96,11 +139,32 @@ impl Quantified {
             quantifiable: Quantifiable::Element(Element::new_literal(lit)),
         }
     }
+    pub(crate) fn from_json_value(json: &Json) -> Result<Self> {
+        Ok(Self {
+            quantifiable: Quantifiable::from_json_value(json.field("quantifiable")?)?,
+            quantifier: Quantifier::from_json_fields(json)?,
+        })
+    }
+    pub(crate) fn to_json_value(&self) -> Json {
+        let mut fields = vec![("quantifiable", self.quantifiable.to_json_value())];
+        match self.quantifier {
+            Some(q) => fields.extend(q.json_fields()),
+            None => fields.push(("quantifier", Json::Null)),
+        }
+        Json::tagged("Quantified", fields)
+    }
     pub fn as_string(&self) -> String {
+        self.as_string_with(&EmitOptions::default())
+    }
+    pub fn as_string_with(&self, opts: &EmitOptions) -> String {
         if let Some(q) = self.quantifier {
-            format!("{}{}", self.quantifiable.as_string(), q.as_string())
+            format!(
+                "{}{}",
+                self.quantifiable.as_string_with(opts),
+                q.as_string()
+            )
         } else {
-            self.quantifiable.as_string()
+            self.quantifiable.as_string_with(opts)
         }
     }
     pub fn flags(&self) -> Flags {
@@ -110,8 +174,60 @@ impl Quantified {
         self.quantifiable.min_match_len()
             * self.quantifier.map(|q| q.min_len_multiplier()).unwrap_or(1)
     }
+    pub(crate) fn tokens(&self, out: &mut Vec<Token>) {
+        match &self.quantifiable {
+            Quantifiable::Element(e) => e.tokens(out),
+            Quantifiable::Group(g) => g.tokens(out),
+        }
+        if let Some(q) = self.quantifier {
+            out.push(Token::Quantifier(q.as_string()));
+        }
+    }
+    /// The longest match, or `None` if unbounded
+    pub fn max_match_len(&self) -> Option<usize> {
+        let len = self.quantifiable.max_match_len()?;
+        match self.quantifier {
+            None => Some(len),
+            Some(_) if len == 0 => Some(0),
+            Some(q) => q.max_count().map(|n| n * len),
+        }
+    }
+    pub fn possible_lengths(&self) -> Option<BTreeSet<usize>> {
+        let lengths = self.quantifiable.possible_lengths()?;
+        let Some(q) = self.quantifier else {
+            return Some(lengths);
+        };
+        if lengths == BTreeSet::from([0]) {
+            return Some(lengths);
+        }
+        let max = q.max_count()?;
+        let min = q.min_len_multiplier();
+        let mut repeated = BTreeSet::from([0]);
+        let mut out = BTreeSet::new();
+        for n in 0..=max {
+            if n > 0 {
+                repeated = sum_lengths(&repeated, &lengths);
+            }
+            if n >= min {
+                out.extend(repeated.iter().copied());
+            }
+        }
+        Some(out)
+    }
+    pub fn fixed_width(&self) -> Option<usize> {
+        let width = self.quantifiable.fixed_width()?;
+        match self.quantifier {
+            None => Some(width),
+            Some(_) if width == 0 => Some(0),
+            Some(q) => q.exact_count().map(|n| n * width),
+        }
+    }
     pub fn is_finite(&self) -> bool {
-        self.quantifier.map(|q| q.is_finite()).unwrap_or(true)
+        let quantifiable_finite = match &self.quantifiable {
+            Quantifiable::Element(_) => true,
+            Quantifiable::Group(g) => g.is_finite(),
+        };
+        quantifiable_finite && self.quantifier.map(|q| q.is_finite()).unwrap_or(true)
     }
     pub(crate) fn without_flag(&self, flag: Flag) -> Self {
         if let Quantifiable::Group(Group::Group {
@@ -129,7 +245,7 @@ impl Quantified {
                     name: name.clone(),
                     components: components.clone(),
                 }),
-                quantifier: self.quantifier.clone(),
+                quantifier: self.quantifier,
             }
         } else {
             self.clone()
@@ -142,14 +258,4 @@ impl Quantified {
             Quantifiable::Group(g) => g.groups_count(),
         }
     }
-    pub(crate) fn nth_group(&self, n: usize) -> Option<Pattern> {
-        if n == 0 {
-            Some(Pattern::Sub(SubPattern::Quantified(self.clone())))
-        } else {
-            match &self.quantifiable {
-                Quantifiable::Element(_) => None,
-                Quantifiable::Group(g) => g.nth_group(n),
-            }
-        }
-    }
 }
@@ -1,8 +1,12 @@
-use crate::{error::ReggieError, parser::Rule};
+use crate::{
+    components::json::{Json, from_name, name_of},
+    error::ReggieError,
+    parser::Rule,
+};
 use anyhow::Result;
 use pest::iterators::{Pair, Pairs};
 
-#[derive(Copy, Clone, Debug, PartialEq)]
+#[derive(Copy, Clone, Debug, PartialEq, Hash)]
 pub enum Q {
     ZeroOrOne,
     ZeroOrMore,
@@ -73,14 +77,22 @@ impl Q {
     }
 }
 
-#[derive(Copy, Clone, Debug, PartialEq)]
+#[derive(Copy, Clone, Debug, PartialEq, Hash)]
 pub enum G {
     Greedy,
     NonGreedy,
     Possessive,
 }
 
-#[derive(Copy, Clone, Debug, PartialEq)]
+impl G {
+    pub(crate) const JSON_NAMES: [(Self, &'static str); 3] = [
+        (Self::Greedy, "Greedy"),
+        (Self::NonGreedy, "NonGreedy"),
+        (Self::Possessive, "Possessive"),
+    ];
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Hash)]
 pub struct Quantifier {
     quantifier: Q,
     greed: G,
@@ -98,34 +110,29 @@ impl Quantifier {
             let mut pair_inner = pair.into_inner();
             if let Rule::quantifier = r {
                 let mut quantifier = None;
-                while let Some(q_match) = pair_inner.next() {
-                    let q_rule = q_match.as_rule();
-                    match q_rule {
+                if let Some(q_match) = pair_inner.next() {
+                    match q_match.as_rule() {
                         Rule::question_mark => {
                             let _ = quantifier.insert(Quantifier::new(Q::ZeroOrOne));
-                            break;
                         }
                         Rule::asterisk => {
                             let _ = quantifier.insert(Quantifier::new(Q::ZeroOrMore));
-                            break;
                         }
                         Rule::plus => {
                             let _ = quantifier.insert(Quantifier::new(Q::OneOrMore));
-                            break;
                         }
                         Rule::l_brace => {
                             let _ = quantifier
                                 .insert(Quantifier::new(Q::n_from_pair(&mut pair_inner, char_ix)?));
-                            break;
                         }
-                        Rule::r_brace => break,
+                        Rule::r_brace => (),
                         _ => {
                             return Err(ReggieError::unexpected_input(q_match).into());
                         }
                     }
                 }
                 let mut quantifier = quantifier.ok_or(ReggieError::unexpected_eoi(char_ix))?;
-                while let Some(greed_match) = pair_inner.next() {
+                for greed_match in pair_inner {
                     match greed_match.as_rule() {
                         Rule::question_mark => quantifier.set_greed(G::NonGreedy),
                         Rule::plus => quantifier.set_greed(G::Possessive),
@@ -139,6 +146,84 @@ impl Quantifier {
             }
         }
     }
+    pub fn exactly(n: usize) -> Self {
+        Self::new(Q::NExact(n))
+    }
+    pub fn at_least(n: usize) -> Self {
+        Self::new(Q::NTimes {
+            min: Some(n),
+            max: None,
+        })
+    }
+    pub fn between(min: usize, max: usize) -> Self {
+        Self::new(Q::NTimes {
+            min: Some(min),
+            max: Some(max),
+        })
+    }
+    pub fn optional() -> Self {
+        Self::new(Q::ZeroOrOne)
+    }
+    pub fn star() -> Self {
+        Self::new(Q::ZeroOrMore)
+    }
+    pub fn plus() -> Self {
+        Self::new(Q::OneOrMore)
+    }
+    pub fn lazy(self) -> Self {
+        Self {
+            greed: G::NonGreedy,
+            ..self
+        }
+    }
+    pub fn possessive(self) -> Self {
+        Self {
+            greed: G::Possessive,
+            ..self
+        }
+    }
+    /// Read the quantifier fields of a `Quantified` object
+    pub(crate) fn from_json_fields(json: &Json) -> Result<Option<Self>> {
+        let Some(kind) = json.opt_field("quantifier")? else {
+            return Ok(None);
+        };
+        let bound = |key: &str| -> Result<Option<usize>> {
+            json.opt_field(key)?.map(Json::as_usize).transpose()
+        };
+        let quantifier = match kind.as_str()? {
+            "ZeroOrOne" => Q::ZeroOrOne,
+            "ZeroOrMore" => Q::ZeroOrMore,
+            "OneOrMore" => Q::OneOrMore,
+            "NExact" => Q::NExact(json.field("count")?.as_usize()?),
+            "NTimes" => Q::NTimes {
+                min: bound("min")?,
+                max: bound("max")?,
+            },
+            other => {
+                return Err(
+                    ReggieError::invalid_json(format!("unknown quantifier {}", other)).into(),
+                );
+            }
+        };
+        let greed = from_name(&G::JSON_NAMES, json.field("greed")?.as_str()?)?;
+        Ok(Some(Self { quantifier, greed }))
+    }
+    /// The fields describing this quantifier within its `Quantified` object
+    pub(crate) fn json_fields(&self) -> Vec<(&'static str, Json)> {
+        let mut fields = match self.quantifier {
+            Q::ZeroOrOne => vec![("quantifier", "ZeroOrOne".into())],
+            Q::ZeroOrMore => vec![("quantifier", "ZeroOrMore".into())],
+            Q::OneOrMore => vec![("quantifier", "OneOrMore".into())],
+            Q::NExact(n) => vec![("quantifier", "NExact".into()), ("count", n.into())],
+            Q::NTimes { min, max } => vec![
+                ("quantifier", "NTimes".into()),
+                ("min", Json::opt(min)),
+                ("max", Json::opt(max)),
+            ],
+        };
+        fields.push(("greed", name_of(&G::JSON_NAMES, &self.greed).into()));
+        fields
+    }
     pub fn as_string(&self) -> String {
         let mut s = match self.quantifier {
             Q::ZeroOrOne => String::from("?"),
@@ -164,15 +249,56 @@ impl Quantifier {
         };
         match self.greed {
             G::NonGreedy => {
-                s.push_str("?");
+                s.push('?');
             }
             G::Possessive => {
-                s.push_str("+");
+                s.push('+');
             }
             _ => (),
         };
         s
     }
+    /// Describe repeating something, given as a `singular` and a `plural`
+    /// phrase, e.g. "between 2 and 4 digits"
+    pub(crate) fn explain(&self, singular: &str, plural: &str) -> String {
+        let mut s = match (self.quantifier, self.exact_count()) {
+            (Q::ZeroOrOne, _) => format!("optionally {}", singular),
+            (Q::ZeroOrMore, _) => format!("zero or more {}", plural),
+            (Q::OneOrMore, _) => format!("one or more {}", plural),
+            (_, Some(1)) => String::from(singular),
+            (_, Some(n)) => format!("exactly {} {}", n, plural),
+            (Q::NTimes { min, max: None }, _) => {
+                format!("at least {} {}", min.unwrap_or_default(), plural)
+            }
+            (
+                Q::NTimes {
+                    min: None | Some(0),
+                    max: Some(max),
+                },
+                _,
+            ) => format!("at most {} {}", max, plural),
+            (
+                Q::NTimes {
+                    min: Some(min),
+                    max: Some(max),
+                },
+                _,
+            ) => format!("between {} and {} {}", min, max, plural),
+            (Q::NExact(n), None) => unreachable!("{{{}}} always has an exact count", n),
+        };
+        match self.greed {
+            G::NonGreedy => s.push_str(" (as few as possible)"),
+            G::Possessive => s.push_str(" (without backtracking)"),
+            G::Greedy => (),
+        }
+        s
+    }
+    pub fn kind(&self) -> Q {
+        self.quantifier
+    }
+    pub fn greediness(&self) -> G {
+        self.greed
+    }
     pub fn is_greedy(&self) -> bool {
         !matches!(self.greed, G::NonGreedy)
     }
@@ -188,6 +314,103 @@ impl Quantifier {
     pub fn set_quantifier(&mut self, quantifier: Q) {
         self.quantifier = quantifier;
     }
+    /// Raise the upper bound by `delta_max`; unbounded quantifiers are unchanged
+    pub fn widen(&self, delta_max: usize) -> Self {
+        let quantifier = match self.quantifier {
+            Q::ZeroOrOne => Q::NTimes {
+                min: Some(0),
+                max: Some(1 + delta_max),
+            },
+            Q::NExact(n) => Q::NTimes {
+                min: Some(n),
+                max: Some(n + delta_max),
+            },
+            Q::NTimes {
+                min,
+                max: Some(max),
+            } => Q::NTimes {
+                min,
+                max: Some(max + delta_max),
+            },
+            other => other,
+        };
+        Self {
+            quantifier,
+            ..*self
+        }
+    }
+    /// Lower the lower bound by `delta_min`, stopping at zero
+    pub fn narrow(&self, delta_min: usize) -> Self {
+        let quantifier = match self.quantifier {
+            Q::OneOrMore if delta_min > 0 => Q::ZeroOrMore,
+            Q::NExact(n) => Q::NTimes {
+                min: Some(n.saturating_sub(delta_min)),
+                max: Some(n),
+            },
+            Q::NTimes {
+                min: Some(min),
+                max,
+            } => Q::NTimes {
+                min: Some(min.saturating_sub(delta_min)),
+                max,
+            },
+            other => other,
+        };
+        Self {
+            quantifier,
+            ..*self
+        }
+    }
+    /// The shorthand spelling of an equivalent brace quantifier, or `None` if
+    /// it's a plain `{1}` that can be dropped. Possessive `{1}+` is kept, as
+    /// it makes the quantified item atomic
+    pub fn normalized(&self) -> Option<Self> {
+        let quantifier = match self.quantifier {
+            Q::NExact(1)
+            | Q::NTimes {
+                min: Some(1),
+                max: Some(1),
+            } if self.greed != G::Possessive => return None,
+            Q::NTimes {
+                min: None | Some(0),
+                max: Some(1),
+            } => Q::ZeroOrOne,
+            Q::NTimes {
+                min: None | Some(0),
+                max: None,
+            } => Q::ZeroOrMore,
+            Q::NTimes {
+                min: Some(1),
+                max: None,
+            } => Q::OneOrMore,
+            other => other,
+        };
+        Some(Self {
+            quantifier,
+            ..*self
+        })
+    }
+    /// The repetition count, if the quantifier always repeats the same
+    /// number of times
+    pub(crate) fn exact_count(&self) -> Option<usize> {
+        match self.quantifier {
+            Q::NExact(n) => Some(n),
+            Q::NTimes {
+                min: Some(min),
+                max: Some(max),
+            } if min == max => Some(min),
+            _ => None,
+        }
+    }
+    /// The most repetitions allowed, if bounded
+    pub(crate) fn max_count(&self) -> Option<usize> {
+        match self.quantifier {
+            Q::ZeroOrOne => Some(1),
+            Q::NExact(n) => Some(n),
+            Q::NTimes { max, .. } => max,
+            Q::ZeroOrMore | Q::OneOrMore => None,
+        }
+    }
     pub(crate) fn min_len_multiplier(&self) -> usize {
         match self.quantifier {
             Q::ZeroOrOne | Q::ZeroOrMore => 0,
@@ -377,4 +600,51 @@ mod test {
             .is_finite()
         );
     }
+    #[test]
+    fn test_quantifier_constructors() {
+        assert_eq!(
+            String::from("{2,4}?"),
+            Quantifier::between(2, 4).lazy().as_string()
+        );
+        assert_eq!(String::from("{3}"), Quantifier::exactly(3).as_string());
+        assert_eq!(String::from("{1,}"), Quantifier::at_least(1).as_string());
+        assert_eq!(
+            String::from("?+"),
+            Quantifier::optional().possessive().as_string()
+        );
+        assert_eq!(String::from("*"), Quantifier::star().as_string());
+        assert_eq!(String::from("+"), Quantifier::plus().as_string());
+    }
+    #[test]
+    fn test_quantifier_accessors() {
+        let q = Quantifier::plus().possessive();
+        assert_eq!(Q::OneOrMore, q.kind());
+        assert_eq!(G::Possessive, q.greediness());
+        let q = Quantifier::between(1, 3);
+        assert_eq!(
+            Q::NTimes {
+                min: Some(1),
+                max: Some(3)
+            },
+            q.kind()
+        );
+        assert_eq!(G::Greedy, q.greediness());
+    }
+    #[test]
+    fn test_brace_quantifier_greediness() {
+        use crate::components::Pattern;
+        let cases = [
+            ("a{2,4}+", Quantifier::between(2, 4).possessive()),
+            ("a{2,4}?", Quantifier::between(2, 4).lazy()),
+            ("a{2,}+", Quantifier::at_least(2).possessive()),
+            ("a{3}+", Quantifier::exactly(3).possessive()),
+        ];
+        for (src, expected) in cases {
+            let p = Pattern::parse(src).unwrap();
+            let quantifiers = p.quantifiers();
+            assert_eq!(1, quantifiers.len(), "{}", src);
+            assert_eq!(&expected, quantifiers[0].0, "{}", src);
+            assert_eq!(String::from(src), p.as_string());
+        }
+    }
 }
@@ -0,0 +1,42 @@
+/// A span of a pattern's canonical text, labelled by what it does, for
+/// syntax highlighting
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Token {
+    /// Whole-pattern flags like `(?i)`, or inline `(?i-s)`
+    Flags(String),
+    GroupOpen(String),
+    GroupClose(String),
+    Literal(String),
+    /// `.`, anchors, backreferences and control verbs
+    Meta(String),
+    /// A shorthand class like `\d`
+    CharClass(String),
+    CharSetOpen(String),
+    CharSetRange(String),
+    CharSetClose(String),
+    Quantifier(String),
+    Alternation(String),
+    Comment(String),
+    /// Source `parse_recover` couldn't parse
+    Error(String),
+}
+
+impl Token {
+    pub fn text(&self) -> &str {
+        match self {
+            Self::Flags(s)
+            | Self::GroupOpen(s)
+            | Self::GroupClose(s)
+            | Self::Literal(s)
+            | Self::Meta(s)
+            | Self::CharClass(s)
+            | Self::CharSetOpen(s)
+            | Self::CharSetRange(s)
+            | Self::CharSetClose(s)
+            | Self::Quantifier(s)
+            | Self::Alternation(s)
+            | Self::Comment(s)
+            | Self::Error(s) => s,
+        }
+    }
+}
@@ -1,5 +1,6 @@
 use crate::parser::Rule;
 use pest::iterators::Pair;
+use std::fmt;
 use thiserror::Error;
 
 #[derive(Error, Debug)]
@@ -16,10 +17,30 @@ pub enum ReggieError {
     InvalidLiteral { bad_literal: String },
     #[error("Invalid ranges {bad_ranges:?}")]
     InvalidRanges { bad_ranges: Vec<(char, char)> },
+    #[error("Invalid range: {detail}")]
+    InvalidRange { detail: String },
     #[error("Invalid character class {bad_cclass}")]
     InvalidCharClass { bad_cclass: String },
+    #[error("Invalid control verb {bad_verb}")]
+    InvalidControlVerb { bad_verb: String },
     #[error("Pattern flags must be positive")]
     NegativePatternFlags,
+    #[error("Backreference to undefined group {reference}")]
+    UndefinedBackreference { reference: String },
+    #[error("No capturing group numbered {number}")]
+    NoSuchGroup { number: usize },
+    #[error("Pattern {pattern} re-parsed as {reparsed}")]
+    RoundTripMismatch { pattern: String, reparsed: String },
+    #[error("Unsupported feature {feature}")]
+    Unsupported { feature: String },
+    #[error("Conflicting flags {left} and {right}")]
+    ConflictingFlags { left: String, right: String },
+    #[error("Invalid JSON: {detail}")]
+    InvalidJson { detail: String },
+    #[error("Invalid group name {name}")]
+    InvalidGroupName { name: String },
+    #[error("RE2 doesn't support {feature}")]
+    Re2Incompatible { feature: String },
 }
 
 impl ReggieError {
@@ -33,4 +54,77 @@ impl ReggieError {
     pub(crate) fn unexpected_eoi(char_ix: usize) -> Self {
         Self::UnexpectedEndOfInput { char_ix }
     }
+    pub(crate) fn invalid_json(detail: impl Into<String>) -> Self {
+        Self::InvalidJson {
+            detail: detail.into(),
+        }
+    }
+    pub fn with_source(self, src: &str) -> ContextualError {
+        ContextualError {
+            error: self,
+            source: String::from(src),
+        }
+    }
+    fn char_ix(&self) -> Option<usize> {
+        match self {
+            Self::UnexpectedInput { char_ix, .. } | Self::UnexpectedEndOfInput { char_ix } => {
+                Some(*char_ix)
+            }
+            _ => None,
+        }
+    }
+}
+
+/// A [`ReggieError`] paired with the pattern it came from, for display to humans
+#[derive(Debug)]
+pub struct ContextualError {
+    error: ReggieError,
+    source: String,
+}
+
+impl ContextualError {
+    pub fn error(&self) -> &ReggieError {
+        &self.error
+    }
+    pub fn source_str(&self) -> &str {
+        &self.source
+    }
+}
+
+impl fmt::Display for ContextualError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        // pest errors already render their own context
+        if let ReggieError::ParsingError(_) = self.error {
+            return write!(f, "{}", self.error);
+        }
+        writeln!(f, "{}", self.error)?;
+        write!(f, "  | {}", self.source)?;
+        if let Some(char_ix) = self.error.char_ix() {
+            // char_ix is 1-based, as reported by pest
+            write!(f, "\n  | {}^", " ".repeat(char_ix.saturating_sub(1)))?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for ContextualError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        Some(&self.error)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_contextual_unexpected_input() {
+        let err = ReggieError::UnexpectedInput {
+            input: "?".into(),
+            char_ix: 3,
+        }
+        .with_source("ab?c");
+        let expected = String::from("Unexpected input ? at character 3\n  | ab?c\n  |   ^");
+        assert_eq!(expected, err.to_string());
+    }
 }
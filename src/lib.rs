@@ -1,3 +1,303 @@
 pub mod components;
 pub mod error;
+pub mod lint;
 pub mod parser;
+
+use anyhow::Result;
+use components::{
+    BytePattern, Element, EmitOptions, EscapeContext, Flags, Group, GroupExt, Pattern,
+    char_set::escape_set_char_with, pattern::SubPattern,
+};
+use error::ReggieError;
+use lint::{Lint, LintConfig};
+use parser::{PyRegexParser, Rule};
+use pest::Parser;
+
+pub fn parse(src: &str) -> Result<Pattern> {
+    Pattern::parse(src)
+}
+
+pub fn parse_bytes(src: &str) -> Result<BytePattern> {
+    BytePattern::parse(src)
+}
+
+/// Parse, rejecting what RE2 can't run: backreferences, conditionals,
+/// lookaround and atomic groups
+pub fn parse_re2_compatible(src: &str) -> Result<Pattern> {
+    let pattern = parse(src)?;
+    let mut feature = None;
+    pattern.walk(&mut |sp| {
+        let kind = match sp {
+            SubPattern::Backref(_) => "backreference",
+            _ => match sp.group() {
+                Some(Group::NamedBackref { .. } | Group::RelativeBackref { .. }) => "backreference",
+                Some(Group::Ternary { .. }) => "conditional",
+                Some(Group::Group {
+                    ext: Some(GroupExt::Atomic),
+                    ..
+                }) => "atomic group",
+                Some(Group::Group {
+                    ext:
+                        Some(
+                            GroupExt::PosLookahead
+                            | GroupExt::NegLookahead
+                            | GroupExt::PosLookbehind
+                            | GroupExt::NegLookbehind,
+                        ),
+                    ..
+                }) => "lookaround",
+                _ => return,
+            },
+        };
+        feature.get_or_insert_with(|| format!("{} {}", kind, sp.as_string()));
+    });
+    match feature {
+        Some(feature) => Err(ReggieError::Re2Incompatible { feature }.into()),
+        None => Ok(pattern),
+    }
+}
+
+/// Just the leading `(?flags)`, without parsing the rest of the pattern.
+/// Empty if there aren't any
+pub fn parse_flags(src: &str) -> Result<Flags> {
+    match PyRegexParser::parse(Rule::whole_pattern_flags, src) {
+        Ok(mut pairs) => match pairs.next() {
+            Some(pair) => Flags::from_whole_pattern_pair(pair),
+            None => Ok(Flags::empty()),
+        },
+        Err(_) => Ok(Flags::empty()),
+    }
+}
+
+/// Parse, reporting `(bytes_consumed, total)` to `on_progress` as each
+/// top-level sub-pattern is built
+pub fn parse_with_progress(src: &str, on_progress: impl FnMut(usize, usize)) -> Result<Pattern> {
+    Pattern::parse_with_progress(src, on_progress)
+}
+
+/// Parse each top-level branch of an alternation as its own pattern, one at a
+/// time, without building the whole alternation first
+pub fn parse_alternation_lazy(src: &str) -> impl Iterator<Item = Result<Pattern>> + '_ {
+    parser::TopLevelBranches::new(src).map(parse)
+}
+
+/// Parse, then run every lint over the result
+pub fn parse_linted(src: &str) -> Result<(Pattern, Vec<Lint>)> {
+    parse_linted_with(src, &LintConfig::default())
+}
+
+pub fn parse_linted_with(src: &str, config: &LintConfig) -> Result<(Pattern, Vec<Lint>)> {
+    let pattern = parse(src)?;
+    let lints = lint::lint(src, &pattern, config);
+    Ok((pattern, lints))
+}
+
+/// Escape `s` so it matches itself literally when placed in `ctx`
+pub fn escape_literal(s: &str, ctx: EscapeContext) -> String {
+    match ctx {
+        EscapeContext::Pattern => Element::new_literal(String::from(s)).as_string(),
+        EscapeContext::CharSetBody => s
+            .chars()
+            .map(|c| escape_set_char_with(c, &EmitOptions::default()))
+            .collect(),
+    }
+}
+
+// stands in for unparseable spans while the rest is parsed
+const RECOVERY_PLACEHOLDER: &str = "(?#?)";
+
+/// Parse as much of `src` as possible, for editors. Top-level groups and
+/// character sets that don't parse on their own become `SubPattern::Error`
+/// nodes, and their errors are collected. If the rest still doesn't parse,
+/// there's no pattern, just the errors
+pub fn parse_recover(src: &str) -> (Option<Pattern>, Vec<ReggieError>) {
+    let first_err = match parse(src) {
+        Ok(p) => return (Some(p), Vec::new()),
+        Err(e) => e,
+    };
+    let mut errors = Vec::new();
+    let mut bad = Vec::new();
+    // every top-level comment in the patched source, in order: the index into
+    // `bad` for placeholders, `None` for the pattern's own comments
+    let mut comments = Vec::new();
+    let mut patched = String::new();
+    let mut last = 0;
+    for span in parser::top_level_constructs(src) {
+        match parse(&src[span.clone()]) {
+            Err(e) => {
+                let char_ix = src[..span.start].chars().count() + 1;
+                errors.push(into_reggie_error(e, char_ix));
+                patched.push_str(&src[last..span.start]);
+                patched.push_str(RECOVERY_PLACEHOLDER);
+                comments.push(Some(bad.len()));
+                bad.push(span.clone());
+                last = span.end;
+            }
+            Ok(Pattern::Pat(p))
+                if matches!(p.sub_patterns.as_slice(), [SubPattern::Comment(_)]) =>
+            {
+                comments.push(None)
+            }
+            Ok(_) => (),
+        }
+    }
+    if bad.is_empty() {
+        return (None, vec![into_reggie_error(first_err, 1)]);
+    }
+    patched.push_str(&src[last..]);
+    match parse(&patched) {
+        Ok(mut p) => {
+            let mut comments = comments.into_iter();
+            let mut restore = |sp: &mut SubPattern| {
+                if let SubPattern::Comment(_) = sp
+                    && let Some(span) = comments.next().flatten().and_then(|n| bad.get(n))
+                {
+                    *sp = SubPattern::Error {
+                        raw: String::from(&src[span.clone()]),
+                        span: (span.start, span.end),
+                    };
+                }
+            };
+            if let Pattern::Pat(pat) = &mut p {
+                for sp in pat.sub_patterns.iter_mut() {
+                    match sp {
+                        SubPattern::Alternatives(alts) => alts.0.iter_mut().for_each(&mut restore),
+                        sp => restore(sp),
+                    }
+                }
+            }
+            (Some(p), errors)
+        }
+        Err(e) => {
+            errors.push(into_reggie_error(e, 1));
+            (None, errors)
+        }
+    }
+}
+
+/// `char_ix` is the 1-based column reported when `err` isn't a `ReggieError`,
+/// matching the parser's own positions
+fn into_reggie_error(err: anyhow::Error, char_ix: usize) -> ReggieError {
+    err.downcast()
+        .unwrap_or_else(|e: anyhow::Error| ReggieError::UnexpectedInput {
+            input: e.to_string(),
+            char_ix,
+        })
+}
+
+/// Parse each pattern independently, keeping going past failures
+pub fn parse_many<I: IntoIterator<Item = S>, S: AsRef<str>>(patterns: I) -> Vec<Result<Pattern>> {
+    patterns.into_iter().map(|p| parse(p.as_ref())).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use components::Flag;
+
+    #[test]
+    fn test_parse_alternation_lazy() {
+        let branches: Vec<Pattern> = parse_alternation_lazy("ab|c+|[de]")
+            .collect::<Result<Vec<Pattern>>>()
+            .unwrap();
+        assert_eq!(3, branches.len());
+        assert_eq!(String::from("c+"), branches[1].as_string());
+        assert_eq!(2, parse_alternation_lazy("(a|b)|c").count());
+    }
+    #[test]
+    fn test_parse_re2_compatible() {
+        assert!(parse_re2_compatible("[a-z]+|\\d{2}").is_ok());
+        for src in ["(a)\\1", "(?=x)y", "(?>a)", "(?P<n>a)(?P=n)"] {
+            let err = parse_re2_compatible(src).unwrap_err();
+            assert!(matches!(
+                err.downcast_ref::<ReggieError>(),
+                Some(ReggieError::Re2Incompatible { .. })
+            ));
+        }
+    }
+    #[test]
+    fn test_parse_flags() {
+        let flags = parse_flags("(?im)abc").unwrap();
+        assert!(flags.contains(Flag::Ignorecase));
+        assert!(flags.contains(Flag::Multiline));
+        assert!(parse_flags("abc").unwrap().is_empty());
+        assert!(parse_flags("(?i:abc)").unwrap().is_empty());
+    }
+    #[test]
+    fn test_escape_literal() {
+        assert_eq!(
+            String::from("a\\.b"),
+            escape_literal("a.b", EscapeContext::Pattern)
+        );
+        assert_eq!(
+            String::from("a\\-b"),
+            escape_literal("a-b", EscapeContext::CharSetBody)
+        );
+        let escaped = escape_literal("(x|y)*", EscapeContext::Pattern);
+        assert_eq!(
+            Some(String::from("(x|y)*")),
+            parse(&escaped).unwrap().as_plain_literal()
+        );
+    }
+    #[test]
+    fn test_parse_linted() {
+        let (pattern, lints) = parse_linted("(a)(?:b)|(a)(?:b)").unwrap();
+        assert_eq!(2, pattern.capture_count());
+        assert!(
+            lints
+                .iter()
+                .any(|l| l.kind == lint::LintKind::DuplicateAlternative)
+        );
+        assert!(parse_linted("(").is_err());
+    }
+    #[test]
+    fn test_parse_many() {
+        let parsed = parse_many(["a+", "(", "[a-z]"]);
+        assert_eq!(3, parsed.len());
+        assert!(parsed[0].is_ok());
+        assert!(parsed[1].is_err());
+        assert!(parsed[2].is_ok());
+    }
+    #[test]
+    fn test_parse_recover() {
+        let (p, errors) = parse_recover("ab(?<x)c[de]");
+        assert_eq!(1, errors.len());
+        let p = p.unwrap();
+        assert_eq!(String::from("ab(?<x)c[d-e]"), p.as_string());
+        assert!(
+            p.tokens()
+                .contains(&components::Token::Error(String::from("(?<x)")))
+        );
+        let mut span = None;
+        p.walk(&mut |sp| {
+            if let SubPattern::Error { span: s, .. } = sp {
+                span = Some(*s);
+            }
+        });
+        assert_eq!(Some((2, 7)), span);
+        let (p, errors) = parse_recover("a(b)");
+        assert!(p.is_some() && errors.is_empty());
+        let (p, errors) = parse_recover("a**");
+        assert!(p.is_none());
+        assert_eq!(1, errors.len());
+        // a comment that looks like a placeholder is still a comment
+        let (p, errors) = parse_recover("(?#reggie-recover:5)(");
+        assert_eq!(1, errors.len());
+        assert_eq!(
+            String::from("(?#reggie-recover:5)("),
+            p.unwrap().as_string()
+        );
+    }
+    #[test]
+    fn test_parse_with_progress() {
+        let mut reports = Vec::new();
+        let p = parse_with_progress("(?i)ab(c|d)+[e-f]", |done, total| {
+            reports.push((done, total))
+        })
+        .unwrap();
+        assert_eq!(parse("(?i)ab(c|d)+[e-f]").unwrap(), p);
+        assert!(!reports.is_empty());
+        assert!(reports.windows(2).all(|w| w[0].0 <= w[1].0));
+        assert_eq!(Some(&(17, 17)), reports.last());
+    }
+}
@@ -0,0 +1,236 @@
+use crate::{
+    components::{
+        Alternatives, Flag, Group, Pattern,
+        pattern::{SubPattern, path_string},
+    },
+    parser::TopLevelBranches,
+};
+use std::ops::Range;
+
+/// Nested unbounded quantifiers at least this deep are reported as a
+/// backtracking risk
+pub const REDOS_STAR_HEIGHT: usize = 2;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum LintKind {
+    UnusedCapture,
+    AmbiguousAlternation,
+    DuplicateAlternative,
+    RedundantEscape,
+    EmptyBranch,
+    RedosRisk,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Lint {
+    pub kind: LintKind,
+    pub message: String,
+    /// Byte offsets into the source, when the lint comes from a known place
+    /// in it
+    pub span: Option<Range<usize>>,
+}
+
+impl Lint {
+    fn new(kind: LintKind, message: String) -> Self {
+        Self {
+            kind,
+            message,
+            span: None,
+        }
+    }
+}
+
+/// Which lints `lint` runs. All are on by default
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct LintConfig {
+    pub unused_captures: bool,
+    pub ambiguous_alternation: bool,
+    pub duplicate_alternatives: bool,
+    pub redundant_escapes: bool,
+    pub empty_branches: bool,
+    pub redos_risk: bool,
+}
+
+impl Default for LintConfig {
+    fn default() -> Self {
+        Self {
+            unused_captures: true,
+            ambiguous_alternation: true,
+            duplicate_alternatives: true,
+            redundant_escapes: true,
+            empty_branches: true,
+            redos_risk: true,
+        }
+    }
+}
+
+/// Run the lints `config` enables over `pattern`, which was parsed from `src`
+pub fn lint(src: &str, pattern: &Pattern, config: &LintConfig) -> Vec<Lint> {
+    let mut lints = Vec::new();
+    if config.unused_captures {
+        for n in pattern.unused_captures() {
+            lints.push(Lint::new(
+                LintKind::UnusedCapture,
+                format!("group {} is never referred to", n),
+            ));
+        }
+    }
+    if config.ambiguous_alternation {
+        for message in pattern.ambiguous_alternation() {
+            lints.push(Lint::new(LintKind::AmbiguousAlternation, message));
+        }
+    }
+    if config.duplicate_alternatives {
+        duplicate_alternatives(src, pattern, &mut lints);
+    }
+    if config.redundant_escapes && !pattern.flags().is_some_and(|f| f.contains(Flag::Verbose)) {
+        redundant_escapes(src, &mut lints);
+    }
+    if config.empty_branches {
+        pattern.walk_paths(&mut Vec::new(), &mut |sp, path| {
+            let SubPattern::Alternatives(Alternatives(branches)) = sp else {
+                return;
+            };
+            for (ix, branch) in branches.iter().enumerate() {
+                if let Some(Group::Group { components, .. }) = branch.group()
+                    && components.is_empty()
+                {
+                    lints.push(Lint::new(
+                        LintKind::EmptyBranch,
+                        format!("{}: branch {} is empty", path_string(path), ix),
+                    ));
+                }
+            }
+        });
+    }
+    if config.redos_risk && pattern.star_height() >= REDOS_STAR_HEIGHT {
+        lints.push(Lint::new(
+            LintKind::RedosRisk,
+            format!(
+                "unbounded quantifiers are nested {} deep",
+                pattern.star_height()
+            ),
+        ));
+    }
+    lints
+}
+
+fn duplicate_alternatives(src: &str, pattern: &Pattern, lints: &mut Vec<Lint>) {
+    pattern.walk_paths(&mut Vec::new(), &mut |sp, path| {
+        let SubPattern::Alternatives(Alternatives(branches)) = sp else {
+            return;
+        };
+        for (ix, branch) in branches.iter().enumerate() {
+            if branches[..ix].contains(branch) {
+                lints.push(Lint::new(
+                    LintKind::DuplicateAlternative,
+                    format!(
+                        "{}: {} repeats an earlier branch",
+                        path_string(path),
+                        branch.as_string()
+                    ),
+                ));
+            }
+        }
+    });
+    // alternation binds tighter than concatenation in the AST, so `ab|ab`
+    // only shows up as a whole-pattern alternation in the source
+    if pattern.alternation_parts().len() > 1 {
+        return;
+    }
+    let mut seen: Vec<Pattern> = Vec::new();
+    let mut start = 0;
+    for branch in TopLevelBranches::new(src) {
+        let span = start..start + branch.len();
+        start = span.end + 1;
+        let Ok(parsed) = Pattern::parse(branch) else {
+            return;
+        };
+        if seen.contains(&parsed) {
+            lints.push(Lint {
+                kind: LintKind::DuplicateAlternative,
+                message: format!("{} repeats an earlier branch", branch),
+                span: Some(span),
+            });
+        }
+        seen.push(parsed);
+    }
+}
+
+/// Backslashed punctuation that means the same thing unescaped
+fn redundant_escapes(src: &str, lints: &mut Vec<Lint>) {
+    let mut in_set = false;
+    let mut chars = src.char_indices();
+    while let Some((ix, c)) = chars.next() {
+        match c {
+            '\\' => {
+                let Some((_, escaped)) = chars.next() else {
+                    return;
+                };
+                let significant: &[char] = if in_set {
+                    &[']', '[', '^', '-', '\\']
+                } else {
+                    &[
+                        '.', '^', '$', '*', '+', '?', '{', '}', '[', ']', '\\', '|', '(', ')',
+                    ]
+                };
+                // `\/` is kept for `/.../`-delimited contexts
+                if escaped.is_ascii_punctuation()
+                    && escaped != '/'
+                    && !significant.contains(&escaped)
+                {
+                    lints.push(Lint {
+                        kind: LintKind::RedundantEscape,
+                        message: format!("\\{} is the same as {}", escaped, escaped),
+                        span: Some(ix..ix + 1 + escaped.len_utf8()),
+                    });
+                }
+            }
+            '[' if !in_set => in_set = true,
+            ']' if in_set => in_set = false,
+            _ => (),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn kinds(src: &str, config: &LintConfig) -> Vec<LintKind> {
+        let p = Pattern::parse(src).unwrap();
+        lint(src, &p, config).iter().map(|l| l.kind).collect()
+    }
+
+    #[test]
+    fn test_lint() {
+        let found = kinds("(a)(?:b)|(a)(?:b)", &LintConfig::default());
+        assert!(found.contains(&LintKind::DuplicateAlternative));
+        assert!(found.contains(&LintKind::UnusedCapture));
+        let quiet = LintConfig {
+            unused_captures: false,
+            ..LintConfig::default()
+        };
+        assert!(!kinds("(a)(?:b)|(a)(?:b)", &quiet).contains(&LintKind::UnusedCapture));
+        assert_eq!(
+            vec![LintKind::RedosRisk],
+            kinds("(?:a+)*", &LintConfig::default())
+        );
+        assert!(kinds("x|x", &LintConfig::default()).contains(&LintKind::DuplicateAlternative));
+        assert_eq!(
+            vec![LintKind::EmptyBranch],
+            kinds("(?:x|(?:))", &LintConfig::default())
+        );
+    }
+    #[test]
+    fn test_redundant_escapes() {
+        let p = Pattern::parse("a\\-b[\\-\\.]").unwrap();
+        let lints = lint("a\\-b[\\-\\.]", &p, &LintConfig::default());
+        let spans: Vec<Option<Range<usize>>> = lints
+            .iter()
+            .filter(|l| l.kind == LintKind::RedundantEscape)
+            .map(|l| l.span.clone())
+            .collect();
+        assert_eq!(vec![Some(1..3), Some(7..9)], spans);
+    }
+}
@@ -1,7 +1,174 @@
 // use pest::pratt_parser::{Assoc, Op, PrattParser};
 use pest_derive::Parser;
+use std::ops::Range;
 // use std::sync::LazyLock;
 
 #[derive(Parser)]
 #[grammar = "pyregex.pest"]
 pub struct PyRegexParser;
+
+/// Apply verbose-mode tokenization: drop unescaped whitespace and `#` comments
+/// outside of character sets
+pub(crate) fn strip_verbose(src: &str) -> String {
+    let mut out = String::with_capacity(src.len());
+    let mut chars = src.chars();
+    let mut in_set = false;
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => {
+                out.push(c);
+                if let Some(escaped) = chars.next() {
+                    out.push(escaped);
+                }
+            }
+            '[' if !in_set => {
+                in_set = true;
+                out.push(c);
+            }
+            ']' if in_set => {
+                in_set = false;
+                out.push(c);
+            }
+            // `(?#...)` is an inline comment group, not the start of a line comment
+            '#' if !in_set && !out.ends_with("(?") => {
+                for skipped in chars.by_ref() {
+                    if skipped == '\n' {
+                        break;
+                    }
+                }
+            }
+            c if c.is_whitespace() && !in_set => continue,
+            c => out.push(c),
+        }
+    }
+    out
+}
+
+/// Splits a pattern on `|`s that aren't inside a group or character set,
+/// one branch at a time
+pub(crate) struct TopLevelBranches<'a> {
+    rest: Option<&'a str>,
+}
+
+impl<'a> TopLevelBranches<'a> {
+    pub(crate) fn new(src: &'a str) -> Self {
+        Self { rest: Some(src) }
+    }
+}
+
+impl<'a> Iterator for TopLevelBranches<'a> {
+    type Item = &'a str;
+
+    fn next(&mut self) -> Option<&'a str> {
+        let src = self.rest?;
+        let mut depth = 0usize;
+        let mut in_set = false;
+        let mut chars = src.char_indices();
+        while let Some((ix, c)) = chars.next() {
+            match c {
+                '\\' => {
+                    chars.next();
+                }
+                '[' if !in_set => in_set = true,
+                ']' if in_set => in_set = false,
+                '(' if !in_set => depth += 1,
+                ')' if !in_set => depth = depth.saturating_sub(1),
+                '|' if !in_set && depth == 0 => {
+                    self.rest = Some(&src[ix + 1..]);
+                    return Some(&src[..ix]);
+                }
+                _ => (),
+            }
+        }
+        self.rest = None;
+        Some(src)
+    }
+}
+
+/// The spans of the groups and character sets that aren't nested in another,
+/// each with any quantifier that follows it. An unclosed one runs to the end
+/// of the source, and a `)` with no `(` is a span of its own
+pub(crate) fn top_level_constructs(src: &str) -> Vec<Range<usize>> {
+    let mut spans = Vec::new();
+    let mut start = None;
+    let mut depth = 0usize;
+    let mut in_set = false;
+    let mut chars = src.char_indices().peekable();
+    while let Some((ix, c)) = chars.next() {
+        let closed = match c {
+            '\\' => {
+                chars.next();
+                false
+            }
+            '[' if !in_set => {
+                in_set = true;
+                start.get_or_insert(ix);
+                false
+            }
+            ']' if in_set => {
+                in_set = false;
+                depth == 0
+            }
+            '(' if !in_set => {
+                depth += 1;
+                start.get_or_insert(ix);
+                false
+            }
+            ')' if !in_set && depth == 0 => {
+                spans.push(ix..ix + 1);
+                false
+            }
+            ')' if !in_set => {
+                depth -= 1;
+                depth == 0
+            }
+            _ => false,
+        };
+        if !closed {
+            continue;
+        }
+        let mut end = ix + 1;
+        while let Some((q_ix, q)) = chars.next_if(|(_, q)| "*+?{".contains(*q)) {
+            end = q_ix + 1;
+            if q == '{' {
+                for (b_ix, b) in chars.by_ref() {
+                    end = b_ix + 1;
+                    if b == '}' {
+                        break;
+                    }
+                }
+            }
+        }
+        spans.extend(start.take().map(|s| s..end));
+    }
+    spans.extend(start.map(|s| s..src.len()));
+    spans
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_strip_verbose() {
+        assert_eq!(
+            String::from("(?x)ab[ #]\\ c"),
+            strip_verbose("(?x) a b  # comment\n [ #] \\ c")
+        );
+        assert_eq!(String::from("(?x)a(?#c)b"), strip_verbose("(?x) a (?#c) b"));
+    }
+    #[test]
+    fn test_top_level_branches() {
+        assert_eq!(
+            vec!["(a|b)", "[|]", "\\|c"],
+            TopLevelBranches::new("(a|b)|[|]|\\|c").collect::<Vec<&str>>()
+        );
+    }
+    #[test]
+    fn test_top_level_constructs() {
+        assert_eq!(
+            vec![1..7, 7..14, 14..15, 16..19],
+            top_level_constructs("a(b|c)+[)(]{2})d(?:")
+        );
+    }
+}